@@ -0,0 +1,29 @@
+use std::env;
+
+// picks the JS engine backend and exposes it as `cfg(engine = "...")`. The browser engine is only
+// available on wasm32 targets with a JS host and v8 only on native targets. Enabling more than one
+// engine for a target is refused in lib.rs rather than resolved here
+fn main() {
+    println!(
+        "cargo:rustc-check-cfg=cfg(engine, values(\"v8\", \"browser\", \"quickjs\", \"boa\"))"
+    );
+    let wasm = env::var("CARGO_CFG_TARGET_ARCH").unwrap() == "wasm32";
+    let wasi = env::var("CARGO_CFG_TARGET_OS").unwrap() == "wasi";
+    let feature = |name: &str| env::var_os(format!("CARGO_FEATURE_{}", name)).is_some();
+    let engine = if feature("QUICKJS") {
+        "quickjs"
+    } else if feature("BOA") {
+        "boa"
+    } else if wasm && !wasi && feature("BROWSER") {
+        "browser"
+    } else if !wasm && feature("V8") {
+        "v8"
+    } else if wasi {
+        panic!("there is no JS host on wasm32-wasi, enable the `quickjs` or `boa` feature");
+    } else if wasm {
+        panic!("enable the `browser`, `quickjs` or `boa` feature on wasm32");
+    } else {
+        panic!("no JS engine feature is enabled, enable `v8`, `quickjs` or `boa`");
+    };
+    println!("cargo:rustc-cfg=engine=\"{}\"", engine);
+}
@@ -0,0 +1,85 @@
+use boa_engine::{realm::Realm, Context};
+
+use crate::thread_state::ThreadState;
+
+thread_state! {
+    static CONTEXT: Option<Context> = None;
+    static BORROWED: bool = false;
+    // boa hands host functions the context it is running on, calls made from inside a host
+    // function have to go through that reference instead of the thread's own one. Each entry is
+    // only lent out once at a time, like the thread's own one with `BORROWED`
+    static STACK: Vec<(*mut Context, bool)> = Vec::new();
+    // the realms that were current before `enter_realm`
+    static REALMS: Vec<Realm> = Vec::new();
+}
+
+// clears the borrow flag when `with_context` returns or unwinds, `None` is the thread's own context
+struct Lent(Option<usize>);
+
+impl Drop for Lent {
+    fn drop(&mut self) {
+        unsafe {
+            match self.0 {
+                Some(index) => STACK.get()[index].1 = false,
+                None => *BORROWED.get() = false,
+            }
+        }
+    }
+}
+
+// lends out the current context for the duration of `f`. Calling it again from inside `f` panics
+// instead of handing out a second `&mut` to the same context, only a host function called by `f`
+// gets to use its own one
+pub(crate) fn with_context<T>(f: impl FnOnce(&mut Context) -> T) -> T {
+    let (context, _lent) = unsafe {
+        if let Some(index) = STACK.get().len().checked_sub(1) {
+            let (context, borrowed) = &mut STACK.get()[index];
+            assert!(!*borrowed, "the boa context is already borrowed");
+            *borrowed = true;
+            (*context, Lent(Some(index)))
+        } else {
+            assert!(!*BORROWED.get(), "the boa context is already borrowed");
+            *BORROWED.get() = true;
+            let context: *mut Context = CONTEXT.get().get_or_insert_with(Context::default);
+            (context, Lent(None))
+        }
+    };
+    f(unsafe { &mut *context })
+}
+
+// `context` is only used through the stack until `leave_call`, boa's own borrow of it is
+// suspended while the host function runs
+pub(crate) fn enter_call(context: &mut Context) {
+    unsafe { STACK.get().push((context, false)) };
+}
+
+pub(crate) fn leave_call() {
+    unsafe { STACK.get().pop() };
+}
+
+pub(crate) fn enter_realm(realm: Realm) {
+    let previous = with_context(|context| context.enter_realm(realm));
+    unsafe { REALMS.get().push(previous) };
+}
+
+pub(crate) fn leave_realm() {
+    if let Some(previous) = unsafe { REALMS.get().pop() } {
+        with_context(|context| context.enter_realm(previous));
+    }
+}
+
+// promise reactions, there is no event loop so they run once control returns to the top-level
+// Rust caller
+pub(crate) fn run_jobs() {
+    if unsafe { STACK.get().is_empty() } {
+        with_context(|context| context.run_jobs());
+    }
+}
+
+pub fn flush() {
+    crate::value::clear_context_state();
+    unsafe {
+        assert!(!*BORROWED.get(), "the boa context is borrowed");
+        *CONTEXT.get() = Some(Context::default());
+    }
+}
@@ -0,0 +1,442 @@
+use std::{collections::HashMap, fs, path::PathBuf};
+
+use crate::{
+    eval, eval_with_filename, global_set, json,
+    thread_state::ThreadState,
+    value::{helper, internal, set_internal},
+    AsObject, Function, Object, Value,
+};
+
+const ERROR: &str = "(function (message, code) {
+    const error = new Error(message);
+    error.code = code;
+    return error;
+})";
+
+// conditions matched in package.json `exports`, in the order the package lists them
+const CONDITIONS: [&str; 3] = ["require", "node", "default"];
+
+thread_state! {
+    static LOADER: Option<Box<dyn ModuleLoader>> = None;
+}
+
+// module paths are absolute and `/`-separated regardless of the platform
+pub trait ModuleLoader {
+    // None when there is no file at `path`
+    fn read(&self, path: &str) -> Option<String>;
+
+    fn exists(&self, path: &str) -> bool {
+        self.read(path).is_some()
+    }
+}
+
+impl<F: Fn(&str) -> Option<String>> ModuleLoader for F {
+    fn read(&self, path: &str) -> Option<String> {
+        (self)(path)
+    }
+}
+
+// serves `/` from a directory on disk
+pub struct FileLoader {
+    root: PathBuf,
+}
+
+impl FileLoader {
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self { root: root.into() }
+    }
+
+    fn path(&self, path: &str) -> PathBuf {
+        self.root.join(path.trim_start_matches('/'))
+    }
+}
+
+impl ModuleLoader for FileLoader {
+    fn read(&self, path: &str) -> Option<String> {
+        fs::read_to_string(self.path(path)).ok()
+    }
+
+    fn exists(&self, path: &str) -> bool {
+        self.path(path).is_file()
+    }
+}
+
+#[derive(Default)]
+pub struct MemoryLoader {
+    files: HashMap<String, String>,
+}
+
+impl MemoryLoader {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_file(mut self, path: impl AsRef<str>, source: impl Into<String>) -> Self {
+        self.insert(path, source);
+        self
+    }
+
+    pub fn insert(&mut self, path: impl AsRef<str>, source: impl Into<String>) {
+        self.files.insert(join("/", path.as_ref()), source.into());
+    }
+}
+
+impl ModuleLoader for MemoryLoader {
+    fn read(&self, path: &str) -> Option<String> {
+        self.files.get(path).cloned()
+    }
+
+    fn exists(&self, path: &str) -> bool {
+        self.files.contains_key(path)
+    }
+}
+
+// also defines the global `require`, which resolves from `/`, and starts with an empty module
+// cache. Both belong to the context, so this has to be called again after `flush`
+pub fn set_loader(loader: impl ModuleLoader + 'static) {
+    unsafe {
+        *LOADER.get() = Some(Box::new(loader));
+    }
+    set_internal("require_cache", Object::new());
+    global_set("require", require_function("/"));
+}
+
+pub fn clear_loader() {
+    unsafe {
+        *LOADER.get() = None;
+    }
+    eval("delete globalThis.require").unwrap();
+}
+
+// loads a module the way a script at `/` would with `require(specifier)`
+pub fn require(specifier: impl AsRef<str>) -> Result<Value, Value> {
+    load(specifier.as_ref(), "/")
+}
+
+fn error(message: String, code: &str) -> Value {
+    helper(ERROR).call([message.into(), code.into()]).unwrap()
+}
+
+// keyed by filename, exposed to scripts as `require.cache`
+fn cache() -> Object {
+    internal("require_cache", || Object::new().into())
+        .into_object()
+        .unwrap()
+}
+
+fn require_function(dirname: &str) -> Function {
+    let require = Function::new_static_with_data(dirname, |args| {
+        let Some(specifier) = args.get(0).into_string() else {
+            return Err(error(
+                "The \"id\" argument must be a string".to_owned(),
+                "ERR_INVALID_ARG_TYPE",
+            ));
+        };
+        load(&specifier, &args.data().into_string().unwrap())
+    });
+    require.as_object().set("cache", cache());
+    require
+}
+
+fn load(specifier: &str, dirname: &str) -> Result<Value, Value> {
+    let Some(loader) = (unsafe { LOADER.get().as_deref() }) else {
+        return Err(error(
+            "No module loader is set.".to_owned(),
+            "MODULE_NOT_FOUND",
+        ));
+    };
+    let Some(filename) = resolve(loader, specifier, dirname) else {
+        return Err(error(
+            format!("Cannot find module '{}' from '{}'", specifier, dirname),
+            "MODULE_NOT_FOUND",
+        ));
+    };
+    let cache = cache();
+    // a module that is still loading is part of a cycle and hands out its exports so far
+    if let Some(module) = cache.get(&filename).into_object() {
+        return Ok(module.get("exports"));
+    }
+    let module = Object::new();
+    module.set("id", filename.as_str());
+    module.set("filename", filename.as_str());
+    module.set("loaded", false);
+    module.set("exports", Object::new());
+    cache.set(&filename, module.clone());
+    if let Err(err) = evaluate(loader, &module, &filename) {
+        cache.delete(&filename);
+        return Err(err);
+    }
+    module.set("loaded", true);
+    Ok(module.get("exports"))
+}
+
+fn evaluate(loader: &dyn ModuleLoader, module: &Object, filename: &str) -> Result<(), Value> {
+    let Some(mut source) = loader.read(filename) else {
+        return Err(error(
+            format!("Cannot read module '{}'", filename),
+            "MODULE_NOT_FOUND",
+        ));
+    };
+    if filename.ends_with(".json") {
+        let Some(exports) = json::parse(&source) else {
+            return Err(error(
+                format!("Invalid JSON in '{}'", filename),
+                "ERR_INVALID_PACKAGE_CONFIG",
+            ));
+        };
+        module.set("exports", exports);
+        return Ok(());
+    }
+    // the line is kept so that line numbers in stack traces still match
+    if source.starts_with("#!") {
+        source.replace_range(..2, "//");
+    }
+    // the wrapper opens on the module's first line, also for line numbers
+    let wrapper = format!(
+        "(function (exports, require, module, __filename, __dirname) {{{}\n}})",
+        source
+    );
+    let function = eval_with_filename(wrapper, filename)?
+        .into_function()
+        .unwrap();
+    let exports = module.get("exports");
+    let dirname = dirname(filename);
+    function.call_with(
+        exports.clone(),
+        [
+            exports,
+            require_function(dirname).into(),
+            module.clone().into(),
+            filename.into(),
+            dirname.into(),
+        ],
+    )?;
+    Ok(())
+}
+
+// https://nodejs.org/api/modules.html#all-together
+fn resolve(loader: &dyn ModuleLoader, specifier: &str, dirname: &str) -> Option<String> {
+    if specifier.starts_with("./")
+        || specifier.starts_with("../")
+        || specifier.starts_with('/')
+        || specifier == "."
+        || specifier == ".."
+    {
+        let path = join(dirname, specifier);
+        return load_as_file(loader, &path).or_else(|| load_as_directory(loader, &path));
+    }
+    // scoped packages take two segments, the rest of the specifier is a path inside the package
+    let name_length = specifier
+        .match_indices('/')
+        .nth(if specifier.starts_with('@') { 1 } else { 0 })
+        .map(|(index, _)| index)
+        .unwrap_or(specifier.len());
+    let (name, subpath) = specifier.split_at(name_length);
+    let mut directory = dirname.to_owned();
+    loop {
+        if !directory.ends_with("/node_modules") {
+            let package = join(&directory, &format!("node_modules/{}", name));
+            if let Some(path) = load_package(loader, &package, subpath) {
+                return Some(path);
+            }
+        }
+        if directory == "/" {
+            return None;
+        }
+        directory = self::dirname(&directory).to_owned();
+    }
+}
+
+fn load_package(loader: &dyn ModuleLoader, package: &str, subpath: &str) -> Option<String> {
+    let exports = package_json(loader, package)
+        .map(|package_json| package_json.get("exports"))
+        .unwrap_or(Value::Undefined);
+    if matches!(exports, Value::Undefined) {
+        let path = format!("{}{}", package, subpath);
+        return load_as_file(loader, &path).or_else(|| load_as_directory(loader, &path));
+    }
+    // with `exports`, only the listed paths can be required and they have to match exactly
+    let target = resolve_exports(&exports, &format!(".{}", subpath))?;
+    let path = join(package, &target);
+    loader.exists(&path).then_some(path)
+}
+
+fn load_as_file(loader: &dyn ModuleLoader, path: &str) -> Option<String> {
+    [
+        path.to_owned(),
+        format!("{}.js", path),
+        format!("{}.json", path),
+    ]
+    .into_iter()
+    .find(|path| loader.exists(path))
+}
+
+fn load_index(loader: &dyn ModuleLoader, path: &str) -> Option<String> {
+    [format!("{}/index.js", path), format!("{}/index.json", path)]
+        .into_iter()
+        .find(|path| loader.exists(path))
+}
+
+fn load_as_directory(loader: &dyn ModuleLoader, path: &str) -> Option<String> {
+    let main =
+        package_json(loader, path).and_then(|package_json| package_json.get("main").into_string());
+    if let Some(main) = main {
+        let main = join(path, &main);
+        if let Some(path) = load_as_file(loader, &main).or_else(|| load_index(loader, &main)) {
+            return Some(path);
+        }
+    }
+    load_index(loader, path)
+}
+
+fn package_json(loader: &dyn ModuleLoader, directory: &str) -> Option<Object> {
+    let source = loader.read(&join(directory, "package.json"))?;
+    json::parse(source)?.into_object()
+}
+
+fn resolve_exports(exports: &Value, subpath: &str) -> Option<String> {
+    // without subpath keys, `exports` only describes the package's main entry point
+    let map = match exports {
+        Value::Object(map) if map.keys().iter().any(|key| key.starts_with('.')) => map,
+        _ if subpath == "." => return resolve_target(exports, ""),
+        _ => return None,
+    };
+    let target = map.get(subpath);
+    if !matches!(target, Value::Undefined) {
+        return resolve_target(&target, "");
+    }
+    // the pattern with the longest prefix wins, e.g. "./utils/*" over "./*"
+    map.keys()
+        .iter()
+        .filter_map(|key| {
+            let (prefix, suffix) = key.split_once('*')?;
+            let matched = subpath.strip_prefix(prefix)?.strip_suffix(suffix)?;
+            Some((prefix.len(), key, matched))
+        })
+        .max_by_key(|(length, _, _)| *length)
+        .and_then(|(_, key, matched)| resolve_target(&map.get(key), matched))
+}
+
+fn resolve_target(target: &Value, matched: &str) -> Option<String> {
+    match target {
+        Value::String(target) => Some(target.replace('*', matched)),
+        Value::Array(targets) => {
+            (0..targets.length()).find_map(|index| resolve_target(&targets.get(index), matched))
+        }
+        Value::Object(conditions) => conditions
+            .keys()
+            .iter()
+            .filter(|key| CONDITIONS.contains(&key.as_str()))
+            .find_map(|key| resolve_target(&conditions.get(key), matched)),
+        _ => None,
+    }
+}
+
+// joins and normalizes `/`-separated paths, `..` stops at the root
+fn join(base: &str, path: &str) -> String {
+    let base = if path.starts_with('/') { "" } else { base };
+    let mut segments = vec![];
+    for segment in base.split('/').chain(path.split('/')) {
+        match segment {
+            "" | "." => {}
+            ".." => {
+                segments.pop();
+            }
+            segment => segments.push(segment),
+        }
+    }
+    format!("/{}", segments.join("/"))
+}
+
+fn dirname(path: &str) -> &str {
+    match path.rfind('/') {
+        Some(0) | None => "/",
+        Some(index) => &path[..index],
+    }
+}
+
+#[cfg(test)]
+mod test {
+    #[cfg(engine = "browser")]
+    use wasm_bindgen_test::wasm_bindgen_test as test;
+
+    use crate::{
+        commonjs::{self, MemoryLoader},
+        eval, Value,
+    };
+
+    #[test]
+    fn relative_and_json() {
+        commonjs::set_loader(
+            MemoryLoader::new()
+                .with_file("/main.js", "const { add } = require('./lib/math'); module.exports = add(1, require('./config').base);")
+                .with_file("/lib/math.js", "exports.add = (a, b) => a + b; exports.dir = __dirname;")
+                .with_file("/config.json", "{ \"base\": 41 }"),
+        );
+        assert_eq!(commonjs::require("./main").unwrap(), Value::from(42.));
+        assert_eq!(
+            eval("require('/lib/math.js').dir").unwrap(),
+            Value::from("/lib")
+        );
+        assert_eq!(
+            eval("Object.keys(require.cache).sort().join()").unwrap(),
+            Value::from("/config.json,/lib/math.js,/main.js")
+        );
+        let error = eval("try { require('./missing') } catch (error) { error.code }").unwrap();
+        assert_eq!(error, Value::from("MODULE_NOT_FOUND"));
+        commonjs::clear_loader();
+    }
+
+    #[test]
+    fn node_modules() {
+        commonjs::set_loader(
+            MemoryLoader::new()
+                .with_file("/app/src/index.js", "module.exports = [require('legacy'), require('modern'), require('modern/feature'), require('@scope/pkg')].join();")
+                .with_file("/app/node_modules/legacy/package.json", "{ \"main\": \"lib/entry\" }")
+                .with_file("/app/node_modules/legacy/lib/entry.js", "module.exports = 'legacy';")
+                .with_file(
+                    "/node_modules/modern/package.json",
+                    "{ \"exports\": { \".\": { \"import\": \"./esm.mjs\", \"require\": \"./cjs.js\" }, \"./*\": \"./features/*.js\" } }",
+                )
+                .with_file("/node_modules/modern/cjs.js", "module.exports = 'modern';")
+                .with_file("/node_modules/modern/features/feature.js", "module.exports = 'feature';")
+                .with_file("/app/node_modules/@scope/pkg/index.js", "module.exports = 'scoped';"),
+        );
+        assert_eq!(
+            commonjs::require("/app/src").unwrap(),
+            Value::from("legacy,modern,feature,scoped")
+        );
+        assert!(eval("require('modern/cjs.js')").is_err());
+        commonjs::clear_loader();
+    }
+
+    #[test]
+    fn cycles() {
+        commonjs::set_loader(
+            MemoryLoader::new()
+                .with_file(
+                    "/a.js",
+                    "exports.early = true; const b = require('./b'); exports.seen = b.seen;",
+                )
+                .with_file("/b.js", "exports.seen = require('./a').early;"),
+        );
+        assert_eq!(eval("require('./a').seen").unwrap(), Value::from(true));
+        commonjs::clear_loader();
+    }
+
+    #[test]
+    fn syntax_errors() {
+        commonjs::set_loader(MemoryLoader::new().with_file("/broken.js", "exports.value = ;"));
+        assert!(commonjs::require("./broken").is_err());
+        assert_eq!(
+            eval("try { require('./broken') } catch (error) { error instanceof SyntaxError }")
+                .unwrap(),
+            Value::from(true)
+        );
+        assert_eq!(
+            eval("'/broken.js' in require.cache").unwrap(),
+            Value::from(false)
+        );
+        commonjs::clear_loader();
+    }
+}
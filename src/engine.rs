@@ -0,0 +1,114 @@
+use crate::{Args, FunctionBuilder, Object, ScriptLocation, Value};
+
+#[cfg(engine = "boa")]
+mod boa;
+#[cfg(engine = "browser")]
+mod browser;
+#[cfg(engine = "quickjs")]
+mod quickjs;
+#[cfg(engine = "v8")]
+mod v8;
+
+#[cfg(engine = "v8")]
+pub(crate) use self::v8::V8 as Current;
+#[cfg(engine = "boa")]
+pub(crate) use boa::Boa as Current;
+#[cfg(engine = "browser")]
+pub(crate) use browser::Browser as Current;
+#[cfg(engine = "quickjs")]
+pub(crate) use quickjs::QuickJs as Current;
+
+pub(crate) type ObjectHandle = <Current as Engine>::Object;
+pub(crate) type ArrayHandle = <Current as Engine>::Array;
+pub(crate) type FunctionHandle = <Current as Engine>::Function;
+
+pub(crate) type HostFunction = fn(Args) -> Result<Value, Value>;
+
+// for engines whose host functions can't see `new.target`, the wrapper passes it along with
+// `this` and the function's data. Both are closed over so scripts can't reach them
+#[cfg(any(engine = "browser", engine = "quickjs", engine = "boa"))]
+const CONSTRUCTOR_WRAPPER: &str = r#"(function (inner, data) {
+    return function wrapper() {
+        return inner.apply(null, [this, new.target, data, Array.from(arguments)]);
+    };
+})"#;
+// methods can't be called with `new`
+#[cfg(any(engine = "browser", engine = "quickjs", engine = "boa"))]
+const METHOD_WRAPPER: &str = r#"(function (inner, data) {
+    const { wrapper } = {
+        wrapper() {
+            return inner.apply(null, [this, undefined, data, Array.from(arguments)]);
+        },
+    };
+    return wrapper;
+})"#;
+#[cfg(any(engine = "browser", engine = "quickjs", engine = "boa"))]
+const TO_STRING_TAG: &str = "(function (f) { return f[Symbol.toStringTag]; })";
+#[cfg(any(engine = "quickjs", engine = "boa"))]
+const TYPE_ERROR: &str = "(function (message) { return new TypeError(message); })";
+
+// symbols and BigInts have no variant, handing one to Rust where that can fail is a TypeError
+#[cfg(any(engine = "quickjs", engine = "boa"))]
+fn unsupported(type_of: &str) -> Value {
+    crate::value::helper(TYPE_ERROR)
+        .call([format!("a {} can't be passed to Rust", type_of).into()])
+        .unwrap()
+}
+
+// the operations `Object`, `Array`, `Function` and `eval` are built on, one implementation per
+// engine, selected with the `engine` cfg that build.rs derives from the enabled features
+pub(crate) trait Engine {
+    type Object: Clone + PartialEq;
+    type Array: Clone + PartialEq;
+    type Function: Clone + PartialEq;
+
+    fn eval(source: &str, filename: Option<&str>) -> Result<Value, Value>;
+    fn flush();
+    fn global_object() -> Self::Object;
+
+    fn object_new() -> Self::Object;
+    fn object_get(object: &Self::Object, key: &str) -> Value;
+    fn object_set(object: &Self::Object, key: &str, value: Value);
+    fn object_delete(object: &Self::Object, key: &str);
+    fn object_keys(object: &Self::Object) -> Vec<String>;
+    fn object_prototype(object: &Self::Object) -> Value;
+    fn object_set_prototype(object: &Self::Object, prototype: Value);
+    fn object_define_property(object: &Self::Object, key: &str, value: Value);
+    // a read-only, non-enumerable and non-configurable property of the global object, fails like
+    // `Object.defineProperty` when the global already has a non-configurable one
+    fn global_define(key: &str, value: Value) -> Result<(), Value>;
+
+    fn array_new(length: u32) -> Self::Array;
+    fn array_length(array: &Self::Array) -> u32;
+    fn array_get(array: &Self::Array, index: u32) -> Value;
+    fn array_set(array: &Self::Array, index: u32, value: Value);
+    fn array_as_object(array: &Self::Array) -> Self::Object;
+
+    fn function_new(builder: FunctionBuilder, body: HostFunction) -> Self::Function;
+    // exceptions thrown by the callee come back as `Err`
+    fn function_call(
+        function: &Self::Function,
+        receiver: Value,
+        args: Vec<Value>,
+    ) -> Result<Value, Value>;
+    fn function_new_instance(function: &Self::Function, args: Vec<Value>) -> Result<Object, Value>;
+    fn function_name(function: &Self::Function) -> String;
+    fn function_is_async(function: &Self::Function) -> bool;
+    fn function_is_generator(function: &Self::Function) -> bool;
+    fn function_script_location(function: &Self::Function) -> Option<ScriptLocation>;
+    fn function_as_object(function: &Self::Function) -> Self::Object;
+}
+
+// engines that can run several contexts on one heap, each with its own global object and
+// built-ins, used by sandboxes
+#[cfg(not(engine = "browser"))]
+pub(crate) trait Contexts: Engine {
+    type Context;
+
+    fn context_new() -> Self::Context;
+    // until `context_leave`, scripts run and objects are created in `context`
+    fn context_enter(context: &Self::Context);
+    fn context_leave();
+    // false when the engine can't block `eval` and `new Function` by itself
+    fn context_set_code_generation(context: &Self::Context, allowed: bool) -> bool;
+}
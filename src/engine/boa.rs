@@ -0,0 +1,359 @@
+use std::path::Path;
+
+use boa_engine::{
+    object::builtins::JsArray, property::PropertyDescriptor, realm::Realm, Context, JsError,
+    JsObject, JsResult, JsString, JsValue, NativeFunction, PropertyKey, Source,
+};
+
+use crate::{
+    boa::with_context,
+    engine::{
+        unsupported, Contexts, Engine, HostFunction, CONSTRUCTOR_WRAPPER, METHOD_WRAPPER,
+        TO_STRING_TAG,
+    },
+    value::{catch_panic, helper},
+    Args, Array, AsObject, Function, FunctionBuilder, Object, ScriptLocation, Value,
+};
+
+const KEYS: &str = "(function (object) { return Object.keys(object); })";
+
+pub(crate) struct Boa;
+
+impl Boa {
+    fn to_string_tag(function: &JsObject) -> Option<String> {
+        let function = Function::from(function.clone());
+        helper(TO_STRING_TAG)
+            .call([function.into()])
+            .ok()?
+            .into_string()
+    }
+}
+
+fn key(key: &str) -> PropertyKey {
+    PropertyKey::from(JsString::from(key))
+}
+
+// symbols and BigInts as their wrapper objects, for conversions that can't fail
+fn boxed(value: JsValue, context: &mut Context) -> JsValue {
+    match value {
+        JsValue::Symbol(..) | JsValue::BigInt(..) => {
+            JsValue::from(value.to_object(context).unwrap())
+        }
+        value => value,
+    }
+}
+
+// boxed while the context is at hand, converting a thrown symbol later would need it again
+fn caught<T>(result: JsResult<T>, context: &mut Context) -> Result<T, Value> {
+    result.map_err(|err| {
+        let exception = err.to_opaque(context);
+        Value::from(boxed(exception, context))
+    })
+}
+
+impl Engine for Boa {
+    type Object = JsObject;
+    type Array = JsObject;
+    type Function = JsObject;
+
+    fn eval(source: &str, filename: Option<&str>) -> Result<Value, Value> {
+        let mut source = Source::from_bytes(source);
+        if let Some(filename) = filename {
+            source = source.with_path(Path::new(filename));
+        }
+        let result =
+            with_context(|context| caught(context.eval(source), context)).and_then(Value::try_from);
+        crate::boa::run_jobs();
+        result
+    }
+
+    fn flush() {
+        crate::boa::flush();
+    }
+
+    fn global_object() -> Self::Object {
+        with_context(|context| context.global_object())
+    }
+
+    fn object_new() -> Self::Object {
+        with_context(|context| JsObject::with_object_proto(context.intrinsics()))
+    }
+
+    fn object_get(object: &Self::Object, name: &str) -> Value {
+        with_context(|context| object.get(key(name), context))
+            .map(Value::from)
+            .unwrap_or(Value::Undefined)
+    }
+
+    fn object_set(object: &Self::Object, name: &str, value: Value) {
+        with_context(|context| object.set(key(name), JsValue::from(value), true, context)).unwrap();
+    }
+
+    fn object_delete(object: &Self::Object, name: &str) {
+        let _ = with_context(|context| object.delete_property_or_throw(key(name), context));
+    }
+
+    // boa's own key list includes non-enumerable properties
+    fn object_keys(object: &Self::Object) -> Vec<String> {
+        let object = Object::from(object.clone());
+        let keys = helper(KEYS).call([object.into()]).unwrap();
+        let Some(keys) = keys.into_array() else {
+            return vec![];
+        };
+        (0..keys.length())
+            .filter_map(|index| keys.get(index).into_string())
+            .collect()
+    }
+
+    fn object_prototype(object: &Self::Object) -> Value {
+        match object.prototype() {
+            Some(prototype) => Value::from(JsValue::from(prototype)),
+            None => Value::Null,
+        }
+    }
+
+    fn object_set_prototype(object: &Self::Object, prototype: Value) {
+        let prototype = JsValue::from(prototype).as_object().cloned();
+        object.set_prototype(prototype);
+    }
+
+    fn object_define_property(object: &Self::Object, name: &str, value: Value) {
+        let descriptor = PropertyDescriptor::builder().value(JsValue::from(value));
+        let _ =
+            with_context(|context| object.define_property_or_throw(key(name), descriptor, context));
+    }
+
+    fn global_define(name: &str, value: Value) -> Result<(), Value> {
+        // spelled out, otherwise an existing property keeps its attributes
+        let descriptor = PropertyDescriptor::builder()
+            .value(JsValue::from(value))
+            .writable(false)
+            .enumerable(false)
+            .configurable(false);
+        with_context(|context| {
+            let global = context.global_object();
+            caught(
+                global.define_property_or_throw(key(name), descriptor, context),
+                context,
+            )
+        })
+        .map(drop)
+    }
+
+    fn array_new(length: u32) -> Self::Array {
+        with_context(|context| {
+            let array = JsObject::from(JsArray::new(context));
+            array.set(key("length"), length, true, context).unwrap();
+            array
+        })
+    }
+
+    fn array_length(array: &Self::Array) -> u32 {
+        with_context(|context| array.get(key("length"), context))
+            .ok()
+            .and_then(|length| length.as_number())
+            .unwrap_or(0.) as u32
+    }
+
+    fn array_get(array: &Self::Array, index: u32) -> Value {
+        with_context(|context| array.get(index, context))
+            .map(Value::from)
+            .unwrap_or(Value::Undefined)
+    }
+
+    fn array_set(array: &Self::Array, index: u32, value: Value) {
+        with_context(|context| array.set(index, JsValue::from(value), true, context)).unwrap();
+    }
+
+    fn array_as_object(array: &Self::Array) -> Self::Object {
+        array.clone()
+    }
+
+    fn function_new(builder: FunctionBuilder, body: HostFunction) -> Self::Function {
+        let native = NativeFunction::from_copy_closure(move |_this, arguments, boa_context| {
+            // conversions can call back into the engine, which has to go through this context
+            crate::boa::enter_call(boa_context);
+            // converting the arguments can fail or panic as well
+            let result = catch_panic(|| {
+                let argument = |index: usize| {
+                    Value::try_from(arguments.get(index).cloned().unwrap_or_default())
+                };
+                let this = argument(0)?;
+                let mut args = Args {
+                    holder: this.clone(),
+                    this,
+                    new_target: argument(1)?,
+                    data: argument(2)?,
+                    args: vec![],
+                };
+                if let Some(JsValue::Object(array)) = arguments.get(3) {
+                    for index in 0..Self::array_length(array) {
+                        let value =
+                            with_context(|context| array.get(index, context)).unwrap_or_default();
+                        args.args.push(Value::try_from(value)?);
+                    }
+                }
+                body(args)
+            });
+            crate::boa::leave_call();
+            match result {
+                Ok(value) => Ok(JsValue::from(value)),
+                Err(err) => Err(JsError::from_opaque(JsValue::from(err))),
+            }
+        });
+        let inner = with_context(|context| JsObject::from(native.to_js_function(context.realm())));
+        let js_wrapper = if builder.constructor {
+            CONSTRUCTOR_WRAPPER
+        } else {
+            METHOD_WRAPPER
+        };
+        let function = helper(js_wrapper)
+            .call([Function::from(inner).into(), builder.data])
+            .unwrap()
+            .into_function()
+            .unwrap();
+        let object = function.as_object();
+        object.define_property("name", builder.name);
+        object.define_property("length", builder.length as f64);
+        function.function
+    }
+
+    fn function_call(
+        function: &Self::Function,
+        receiver: Value,
+        args: Vec<Value>,
+    ) -> Result<Value, Value> {
+        let args = args.into_iter().map(JsValue::from).collect::<Vec<_>>();
+        with_context(|context| {
+            caught(
+                function.call(&JsValue::from(receiver), &args, context),
+                context,
+            )
+        })
+        .and_then(Value::try_from)
+    }
+
+    fn function_new_instance(function: &Self::Function, args: Vec<Value>) -> Result<Object, Value> {
+        let args = args.into_iter().map(JsValue::from).collect::<Vec<_>>();
+        with_context(|context| caught(function.construct(&args, None, context), context))
+            .map(Object::from)
+    }
+
+    fn function_name(function: &Self::Function) -> String {
+        Self::object_get(function, "name")
+            .into_string()
+            .unwrap_or_default()
+    }
+
+    fn function_is_async(function: &Self::Function) -> bool {
+        matches!(
+            Self::to_string_tag(function).as_deref(),
+            Some("AsyncFunction" | "AsyncGeneratorFunction")
+        )
+    }
+
+    fn function_is_generator(function: &Self::Function) -> bool {
+        matches!(
+            Self::to_string_tag(function).as_deref(),
+            Some("GeneratorFunction" | "AsyncGeneratorFunction")
+        )
+    }
+
+    fn function_script_location(_function: &Self::Function) -> Option<ScriptLocation> {
+        None
+    }
+
+    fn function_as_object(function: &Self::Function) -> Self::Object {
+        function.clone()
+    }
+}
+
+// realms share boa's heap and each come with their own global object and built-ins
+impl Contexts for Boa {
+    type Context = Realm;
+
+    fn context_new() -> Self::Context {
+        with_context(|context| context.create_realm()).unwrap()
+    }
+
+    fn context_enter(context: &Self::Context) {
+        crate::boa::enter_realm(context.clone());
+    }
+
+    fn context_leave() {
+        crate::boa::leave_realm();
+    }
+
+    fn context_set_code_generation(_context: &Self::Context, _allowed: bool) -> bool {
+        false
+    }
+}
+
+// symbols and BigInts have no variant, handing one to Rust where that can fail is a TypeError
+impl TryFrom<JsValue> for Value {
+    type Error = Value;
+
+    fn try_from(value: JsValue) -> Result<Self, Value> {
+        match value {
+            JsValue::Undefined => Ok(Self::Undefined),
+            JsValue::Null => Ok(Self::Null),
+            JsValue::Boolean(value) => Ok(Self::Boolean(value)),
+            JsValue::Integer(value) => Ok(Self::Number(value as f64)),
+            JsValue::Rational(value) => Ok(Self::Number(value)),
+            JsValue::String(value) => Ok(Self::String(value.to_std_string_escaped())),
+            JsValue::Object(object) if object.is_callable() => {
+                Ok(Self::Function(Function::from(object)))
+            }
+            JsValue::Object(object) if object.is_array() => Ok(Self::Array(Array::from(object))),
+            JsValue::Object(object) => Ok(Self::Object(Object::from(object))),
+            value => Err(unsupported(value.type_of())),
+        }
+    }
+}
+
+// where the conversion can't fail, symbols and BigInts become their wrapper objects. Scripts still
+// treat those like the primitive as property keys and in arithmetic
+impl From<JsValue> for Value {
+    fn from(value: JsValue) -> Self {
+        let value = match value {
+            JsValue::Symbol(..) | JsValue::BigInt(..) => {
+                with_context(|context| boxed(value, context))
+            }
+            value => value,
+        };
+        Self::try_from(value).unwrap()
+    }
+}
+
+impl From<Value> for JsValue {
+    fn from(value: Value) -> Self {
+        match value {
+            Value::Undefined => JsValue::undefined(),
+            Value::Null => JsValue::null(),
+            Value::Boolean(value) => JsValue::from(value),
+            Value::Number(value) => JsValue::from(value),
+            Value::String(value) => JsValue::from(JsString::from(value.as_str())),
+            Value::Array(value) => JsValue::from(value.array),
+            Value::Object(value) => JsValue::from(value.object),
+            Value::Function(value) => JsValue::from(value.function),
+        }
+    }
+}
+
+impl From<JsObject> for Object {
+    fn from(object: JsObject) -> Self {
+        Self { object }
+    }
+}
+
+impl From<JsObject> for Array {
+    fn from(array: JsObject) -> Self {
+        Self { array }
+    }
+}
+
+impl From<JsObject> for Function {
+    fn from(function: JsObject) -> Self {
+        Self { function }
+    }
+}
@@ -0,0 +1,352 @@
+use std::collections::HashMap;
+
+use wasm_bindgen::{closure::Closure, JsValue};
+
+use crate::{
+    engine::{Engine, HostFunction, CONSTRUCTOR_WRAPPER, METHOD_WRAPPER, TO_STRING_TAG},
+    thread_state::ThreadState,
+    value::{catch_panic, helper},
+    Args, Array, AsObject, Function, FunctionBuilder, Object, ScriptLocation, Value,
+};
+
+thread_state! {
+    // one JS function per Rust function body, shared by all the wrappers built on it
+    static STATIC_FUNCTIONS: Option<HashMap<usize, Function>> = None;
+}
+
+pub(crate) struct Browser;
+
+impl Browser {
+    fn to_string_tag(function: &js_sys::Function) -> Option<String> {
+        let function = Function::from(function.clone());
+        helper(TO_STRING_TAG)
+            .call([function.into()])
+            .ok()?
+            .into_string()
+    }
+}
+
+impl Engine for Browser {
+    type Object = js_sys::Object;
+    type Array = js_sys::Array;
+    type Function = js_sys::Function;
+
+    fn eval(source: &str, filename: Option<&str>) -> Result<Value, Value> {
+        let source = match filename {
+            Some(filename) => format!("{}\n//# sourceURL={}", source, filename),
+            None => source.to_owned(),
+        };
+        match js_sys::eval(&source) {
+            Ok(value) => Value::try_from(value),
+            Err(value) => Err(Value::from(value)),
+        }
+    }
+
+    fn flush() {}
+
+    fn global_object() -> Self::Object {
+        js_sys::global()
+    }
+
+    fn object_new() -> Self::Object {
+        js_sys::Object::new()
+    }
+
+    fn object_get(object: &Self::Object, key: &str) -> Value {
+        let key = JsValue::from_str(key);
+        js_sys::Reflect::get(object, &key)
+            .map(Value::from)
+            .unwrap_or(Value::Undefined)
+    }
+
+    fn object_set(object: &Self::Object, key: &str, value: Value) {
+        let key = JsValue::from_str(key);
+        // TODO: don't unwrap
+        js_sys::Reflect::set(object, &key, &JsValue::from(value)).unwrap();
+    }
+
+    fn object_delete(object: &Self::Object, key: &str) {
+        let key = JsValue::from_str(key);
+        // TODO: don't unwrap
+        js_sys::Reflect::delete_property(object, &key).unwrap();
+    }
+
+    // every own string key, non-enumerable ones included
+    fn object_keys(object: &Self::Object) -> Vec<String> {
+        let mut keys = vec![];
+        let object_keys = js_sys::Reflect::own_keys(&object.clone().into()).unwrap();
+        for item in object_keys {
+            if let Some(name) = item.as_string() {
+                keys.push(name);
+            }
+        }
+        keys
+    }
+
+    fn object_prototype(object: &Self::Object) -> Value {
+        Value::from(JsValue::from(js_sys::Object::get_prototype_of(object)))
+    }
+
+    fn object_set_prototype(object: &Self::Object, prototype: Value) {
+        let prototype = js_sys::Object::from(JsValue::from(prototype));
+        js_sys::Object::set_prototype_of(object, &prototype);
+    }
+
+    fn object_define_property(object: &Self::Object, key: &str, value: Value) {
+        let key = JsValue::from(Value::from(key));
+        let attributes = Object::new();
+        attributes.set("value", value);
+        let _ = js_sys::Reflect::define_property(object, &key, &js_sys::Object::from(attributes));
+    }
+
+    fn global_define(key: &str, value: Value) -> Result<(), Value> {
+        let name = JsValue::from(Value::from(key));
+        // spelled out, otherwise an existing property keeps its attributes
+        let attributes = Object::new();
+        attributes.set("value", value);
+        attributes.set("writable", false);
+        attributes.set("enumerable", false);
+        attributes.set("configurable", false);
+        match js_sys::Reflect::define_property(
+            &js_sys::global(),
+            &name,
+            &js_sys::Object::from(attributes),
+        ) {
+            Ok(true) => Ok(()),
+            Ok(false) => Err(Value::from(JsValue::from(js_sys::TypeError::new(
+                &format!("Cannot redefine property: {}", key),
+            )))),
+            Err(err) => Err(Value::from(err)),
+        }
+    }
+
+    fn array_new(length: u32) -> Self::Array {
+        js_sys::Array::new_with_length(length)
+    }
+
+    fn array_length(array: &Self::Array) -> u32 {
+        array.length()
+    }
+
+    fn array_get(array: &Self::Array, index: u32) -> Value {
+        Value::from(array.get(index))
+    }
+
+    fn array_set(array: &Self::Array, index: u32, value: Value) {
+        array.set(index, JsValue::from(value))
+    }
+
+    fn array_as_object(array: &Self::Array) -> Self::Object {
+        js_sys::Object::from(array.clone())
+    }
+
+    fn function_new(builder: FunctionBuilder, body: HostFunction) -> Self::Function {
+        let static_functions = unsafe { STATIC_FUNCTIONS.get().get_or_insert_with(HashMap::new) };
+        let inner_function = if let Some(function) = static_functions.get(&(body as usize)) {
+            function.clone()
+        } else {
+            let bindgen_closure = Closure::<
+                dyn Fn(JsValue, JsValue, JsValue, JsValue) -> Result<JsValue, JsValue>,
+            >::new(
+                move |js_this: JsValue,
+                      js_new_target: JsValue,
+                      js_data: JsValue,
+                      js_args: JsValue| {
+                    // converting the arguments can fail or panic as well
+                    let result = catch_panic(|| {
+                        let this = Value::try_from(js_this)?;
+                        let data = Value::from(js_data);
+                        let mut args = Args {
+                            holder: this.clone(),
+                            this,
+                            new_target: Value::from(js_new_target),
+                            data,
+                            args: vec![],
+                        };
+                        let js_args_array: js_sys::Array = js_args.into();
+                        for i in 0..js_args_array.length() {
+                            args.args.push(Value::try_from(js_args_array.get(i))?);
+                        }
+                        body(args)
+                    });
+                    match result {
+                        Ok(value) => Ok(JsValue::from(value)),
+                        Err(err) => Err(JsValue::from(err)),
+                    }
+                },
+            );
+            let function = Value::from(JsValue::from(bindgen_closure.as_ref()))
+                .into_function()
+                .unwrap();
+            bindgen_closure.forget();
+            static_functions.insert(body as usize, function.clone());
+            function
+        };
+        let js_wrapper = if builder.constructor {
+            CONSTRUCTOR_WRAPPER
+        } else {
+            METHOD_WRAPPER
+        };
+        let function = helper(js_wrapper)
+            .call([inner_function.into(), builder.data])
+            .unwrap()
+            .into_function()
+            .unwrap();
+        let object = function.as_object();
+        object.define_property("name", builder.name);
+        object.define_property("length", builder.length as f64);
+        function.function
+    }
+
+    fn function_call(
+        function: &Self::Function,
+        receiver: Value,
+        args: Vec<Value>,
+    ) -> Result<Value, Value> {
+        let receiver = JsValue::from(receiver);
+        let array = js_sys::Array::new();
+        for arg in args {
+            array.push(&JsValue::from(arg));
+        }
+        match function.apply(&receiver, &array) {
+            Ok(value) => Value::try_from(value),
+            Err(value) => Err(Value::from(value)),
+        }
+    }
+
+    fn function_new_instance(function: &Self::Function, args: Vec<Value>) -> Result<Object, Value> {
+        let array = js_sys::Array::new();
+        for arg in args {
+            array.push(&JsValue::from(arg));
+        }
+        match js_sys::Reflect::construct(function, &array) {
+            Ok(object) => Ok(Object::from(js_sys::Object::from(object))),
+            Err(err) => Err(Value::from(err)),
+        }
+    }
+
+    fn function_name(function: &Self::Function) -> String {
+        String::from(function.name())
+    }
+
+    fn function_is_async(function: &Self::Function) -> bool {
+        matches!(
+            Self::to_string_tag(function).as_deref(),
+            Some("AsyncFunction" | "AsyncGeneratorFunction")
+        )
+    }
+
+    fn function_is_generator(function: &Self::Function) -> bool {
+        matches!(
+            Self::to_string_tag(function).as_deref(),
+            Some("GeneratorFunction" | "AsyncGeneratorFunction")
+        )
+    }
+
+    // browsers don't expose where a function was defined
+    fn function_script_location(_function: &Self::Function) -> Option<ScriptLocation> {
+        None
+    }
+
+    fn function_as_object(function: &Self::Function) -> Self::Object {
+        js_sys::Object::from(function.clone())
+    }
+}
+
+// symbols and BigInts have no variant, handing one to Rust where that can fail is a TypeError
+impl TryFrom<JsValue> for Value {
+    type Error = Value;
+
+    fn try_from(value: JsValue) -> Result<Self, Value> {
+        if value.is_undefined() {
+            Ok(Value::Undefined)
+        } else if value.is_null() {
+            Ok(Value::Null)
+        } else if let Some(value) = value.as_bool() {
+            Ok(Self::Boolean(value))
+        } else if let Some(value) = value.as_f64() {
+            Ok(Self::Number(value))
+        } else if let Some(value) = value.as_string() {
+            Ok(Self::String(value))
+        } else if value.is_function() {
+            Ok(Self::Function(Function::from(js_sys::Function::from(
+                value,
+            ))))
+        } else if value.is_array() {
+            Ok(Self::Array(Array::from(js_sys::Array::from(&value))))
+        } else if value.is_object() {
+            Ok(Self::Object(Object::from(js_sys::Object::from(value))))
+        } else {
+            let message = format!(
+                "a {} can't be passed to Rust",
+                value.js_typeof().as_string().unwrap_or_default()
+            );
+            Err(Value::from(JsValue::from(js_sys::TypeError::new(&message))))
+        }
+    }
+}
+
+// where the conversion can't fail, symbols and BigInts become their wrapper objects. Scripts still
+// treat those like the primitive as property keys and in arithmetic
+impl From<JsValue> for Value {
+    fn from(value: JsValue) -> Self {
+        Self::try_from(value.clone()).unwrap_or_else(|_| {
+            let object = js_sys::Reflect::get(&js_sys::global(), &JsValue::from_str("Object"))
+                .map(js_sys::Function::from)
+                .and_then(|object| object.call1(&JsValue::undefined(), &value))
+                .unwrap();
+            Self::Object(Object::from(js_sys::Object::from(object)))
+        })
+    }
+}
+
+impl From<Value> for JsValue {
+    fn from(value: Value) -> Self {
+        match value {
+            Value::Undefined => JsValue::undefined(),
+            Value::Null => JsValue::null(),
+            Value::Boolean(value) => JsValue::from_bool(value),
+            Value::Number(value) => JsValue::from_f64(value),
+            Value::String(value) => JsValue::from_str(&value),
+            Value::Array(value) => JsValue::from(js_sys::Array::from(value)),
+            Value::Object(value) => JsValue::from(js_sys::Object::from(value)),
+            Value::Function(value) => JsValue::from(js_sys::Function::from(value)),
+        }
+    }
+}
+
+impl From<js_sys::Object> for Object {
+    fn from(object: js_sys::Object) -> Self {
+        Self { object }
+    }
+}
+
+impl From<Object> for js_sys::Object {
+    fn from(object: Object) -> Self {
+        object.object
+    }
+}
+
+impl From<js_sys::Array> for Array {
+    fn from(array: js_sys::Array) -> Self {
+        Self { array }
+    }
+}
+
+impl From<Array> for js_sys::Array {
+    fn from(array: Array) -> Self {
+        array.array
+    }
+}
+
+impl From<js_sys::Function> for Function {
+    fn from(function: js_sys::Function) -> Self {
+        Self { function }
+    }
+}
+
+impl From<Function> for js_sys::Function {
+    fn from(function: Function) -> Self {
+        function.function
+    }
+}
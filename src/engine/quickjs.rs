@@ -0,0 +1,360 @@
+use std::ffi::CString;
+
+use rquickjs::function::{Rest, This};
+
+use crate::{
+    engine::{
+        unsupported, Contexts, Engine, HostFunction, CONSTRUCTOR_WRAPPER, METHOD_WRAPPER,
+        TO_STRING_TAG,
+    },
+    quickjs::{ctx, SandboxContext},
+    value::{catch_panic, helper},
+    Args, Array, AsObject, Function, FunctionBuilder, Object, ScriptLocation, Value,
+};
+
+const DEFINE_PROPERTY: &str =
+    "(function (object, key, value) { Object.defineProperty(object, key, { value }); })";
+const GLOBAL_DEFINE: &str = "(function (key, value) {
+    Object.defineProperty(globalThis, key, {
+        value,
+        writable: false,
+        enumerable: false,
+        configurable: false,
+    });
+})";
+const CONSTRUCT: &str = "(function (f, args) { return Reflect.construct(f, args); })";
+
+type JsValue = rquickjs::Value<'static>;
+
+pub(crate) struct QuickJs;
+
+impl QuickJs {
+    fn to_string_tag(function: &rquickjs::Function<'static>) -> Option<String> {
+        let function = Function::from(function.clone());
+        helper(TO_STRING_TAG)
+            .call([function.into()])
+            .ok()?
+            .into_string()
+    }
+}
+
+// exceptions are stored on the context, other errors (e.g. failed conversions) become strings
+fn caught<T>(result: rquickjs::Result<T>) -> Result<T, Value> {
+    match result {
+        Ok(value) => Ok(value),
+        Err(rquickjs::Error::Exception) => Err(Value::from(ctx().catch())),
+        Err(err) => Err(Value::String(err.to_string())),
+    }
+}
+
+// rquickjs names every script it evaluates "eval_script", so scripts go to QuickJS directly to
+// keep the filename in stack traces
+fn eval_script(source: &str, filename: &str) -> rquickjs::Result<JsValue> {
+    let ctx = ctx();
+    let length = source.len();
+    let source = CString::new(source)?;
+    let filename = CString::new(filename)?;
+    let value = unsafe {
+        JsValue::from_raw(
+            ctx.clone(),
+            rquickjs::qjs::JS_Eval(
+                ctx.as_raw().as_ptr(),
+                source.as_ptr(),
+                length as _,
+                filename.as_ptr(),
+                rquickjs::qjs::JS_EVAL_TYPE_GLOBAL as _,
+            ),
+        )
+    };
+    if value.is_exception() {
+        Err(rquickjs::Error::Exception)
+    } else {
+        Ok(value)
+    }
+}
+
+impl Engine for QuickJs {
+    type Object = rquickjs::Object<'static>;
+    type Array = rquickjs::Array<'static>;
+    type Function = rquickjs::Function<'static>;
+
+    fn eval(source: &str, filename: Option<&str>) -> Result<Value, Value> {
+        let result = caught(eval_script(source, filename.unwrap_or("eval_script")))
+            .and_then(Value::try_from);
+        crate::quickjs::run_pending_jobs();
+        result
+    }
+
+    fn flush() {
+        crate::quickjs::flush();
+    }
+
+    fn global_object() -> Self::Object {
+        ctx().globals()
+    }
+
+    fn object_new() -> Self::Object {
+        rquickjs::Object::new(ctx()).unwrap()
+    }
+
+    fn object_get(object: &Self::Object, key: &str) -> Value {
+        object
+            .get::<_, JsValue>(key)
+            .map(Value::from)
+            .unwrap_or(Value::Undefined)
+    }
+
+    fn object_set(object: &Self::Object, key: &str, value: Value) {
+        object.set(key, JsValue::from(value)).unwrap();
+    }
+
+    fn object_delete(object: &Self::Object, key: &str) {
+        let _ = object.remove(key);
+    }
+
+    fn object_keys(object: &Self::Object) -> Vec<String> {
+        object.keys::<String>().filter_map(Result::ok).collect()
+    }
+
+    fn object_prototype(object: &Self::Object) -> Value {
+        match object.get_prototype() {
+            Some(prototype) => Value::from(prototype.into_value()),
+            None => Value::Null,
+        }
+    }
+
+    fn object_set_prototype(object: &Self::Object, prototype: Value) {
+        let prototype = JsValue::from(prototype).into_object();
+        object.set_prototype(prototype.as_ref()).unwrap();
+    }
+
+    fn object_define_property(object: &Self::Object, key: &str, value: Value) {
+        let object = Object::from(object.clone());
+        let _ = helper(DEFINE_PROPERTY).call([object.into(), key.into(), value]);
+    }
+
+    fn global_define(key: &str, value: Value) -> Result<(), Value> {
+        helper(GLOBAL_DEFINE).call([key.into(), value]).map(drop)
+    }
+
+    fn array_new(length: u32) -> Self::Array {
+        let array = rquickjs::Array::new(ctx()).unwrap();
+        let object: &rquickjs::Object<'static> = &array;
+        object.set("length", length).unwrap();
+        array
+    }
+
+    fn array_length(array: &Self::Array) -> u32 {
+        array.len() as u32
+    }
+
+    fn array_get(array: &Self::Array, index: u32) -> Value {
+        array
+            .get::<JsValue>(index as usize)
+            .map(Value::from)
+            .unwrap_or(Value::Undefined)
+    }
+
+    fn array_set(array: &Self::Array, index: u32, value: Value) {
+        array.set(index as usize, JsValue::from(value)).unwrap();
+    }
+
+    fn array_as_object(array: &Self::Array) -> Self::Object {
+        let object: &rquickjs::Object<'static> = array;
+        object.clone()
+    }
+
+    fn function_new(builder: FunctionBuilder, body: HostFunction) -> Self::Function {
+        let inner = rquickjs::Function::new(
+            ctx(),
+            move |call_ctx: rquickjs::Ctx<'static>,
+                  this: JsValue,
+                  new_target: JsValue,
+                  data: JsValue,
+                  arguments: rquickjs::Array<'static>| {
+                crate::quickjs::enter_call();
+                crate::quickjs::enter_context(call_ctx);
+                // converting the arguments can fail or panic as well
+                let result = catch_panic(|| {
+                    let this = Value::try_from(this)?;
+                    let args = Args {
+                        holder: this.clone(),
+                        this,
+                        new_target: Value::from(new_target),
+                        data: Value::from(data),
+                        args: (0..arguments.len())
+                            .map(|i| Value::try_from(arguments.get::<JsValue>(i).unwrap()))
+                            .collect::<Result<_, _>>()?,
+                    };
+                    body(args)
+                });
+                crate::quickjs::leave_context();
+                crate::quickjs::leave_call();
+                match result {
+                    Ok(value) => Ok(JsValue::from(value)),
+                    Err(err) => Err(ctx().throw(JsValue::from(err))),
+                }
+            },
+        )
+        .unwrap();
+        let js_wrapper = if builder.constructor {
+            CONSTRUCTOR_WRAPPER
+        } else {
+            METHOD_WRAPPER
+        };
+        let function = helper(js_wrapper)
+            .call([Function::from(inner).into(), builder.data])
+            .unwrap()
+            .into_function()
+            .unwrap();
+        let object = function.as_object();
+        object.define_property("name", builder.name);
+        object.define_property("length", builder.length as f64);
+        function.function
+    }
+
+    fn function_call(
+        function: &Self::Function,
+        receiver: Value,
+        args: Vec<Value>,
+    ) -> Result<Value, Value> {
+        let args = args.into_iter().map(JsValue::from).collect::<Vec<_>>();
+        caught(function.call::<_, JsValue>((This(JsValue::from(receiver)), Rest(args))))
+            .and_then(Value::try_from)
+    }
+
+    fn function_new_instance(function: &Self::Function, args: Vec<Value>) -> Result<Object, Value> {
+        let function = Function::from(function.clone());
+        let object = helper(CONSTRUCT).call([function.into(), Value::from(args)])?;
+        Ok(object.into_object().unwrap())
+    }
+
+    fn function_name(function: &Self::Function) -> String {
+        function.get::<_, String>("name").unwrap_or_default()
+    }
+
+    fn function_is_async(function: &Self::Function) -> bool {
+        matches!(
+            Self::to_string_tag(function).as_deref(),
+            Some("AsyncFunction" | "AsyncGeneratorFunction")
+        )
+    }
+
+    fn function_is_generator(function: &Self::Function) -> bool {
+        matches!(
+            Self::to_string_tag(function).as_deref(),
+            Some("GeneratorFunction" | "AsyncGeneratorFunction")
+        )
+    }
+
+    fn function_script_location(_function: &Self::Function) -> Option<ScriptLocation> {
+        None
+    }
+
+    fn function_as_object(function: &Self::Function) -> Self::Object {
+        let object: &rquickjs::Object<'static> = function;
+        object.clone()
+    }
+}
+
+impl Contexts for QuickJs {
+    type Context = SandboxContext;
+
+    fn context_new() -> Self::Context {
+        SandboxContext::new()
+    }
+
+    fn context_enter(context: &Self::Context) {
+        crate::quickjs::enter_context(context.ctx.clone());
+    }
+
+    fn context_leave() {
+        crate::quickjs::leave_context();
+    }
+
+    fn context_set_code_generation(_context: &Self::Context, _allowed: bool) -> bool {
+        false
+    }
+}
+
+// symbols and BigInts have no variant, handing one to Rust where that can fail is a TypeError
+impl TryFrom<JsValue> for Value {
+    type Error = Value;
+
+    fn try_from(value: JsValue) -> Result<Self, Value> {
+        if value.is_undefined() {
+            Ok(Self::Undefined)
+        } else if value.is_null() {
+            Ok(Self::Null)
+        } else if let Some(value) = value.as_bool() {
+            Ok(Self::Boolean(value))
+        } else if let Some(value) = value.as_number() {
+            Ok(Self::Number(value))
+        } else if let Some(string) = value.as_string() {
+            Ok(Self::String(string.to_string().unwrap_or_default()))
+        } else if value.is_function() {
+            Ok(Self::Function(Function::from(
+                value.into_function().unwrap(),
+            )))
+        } else if value.is_array() {
+            Ok(Self::Array(Array::from(value.into_array().unwrap())))
+        } else if value.is_object() {
+            Ok(Self::Object(Object::from(value.into_object().unwrap())))
+        } else {
+            Err(unsupported(
+                &format!("{:?}", value.type_of()).to_lowercase(),
+            ))
+        }
+    }
+}
+
+// where the conversion can't fail, symbols and BigInts become their wrapper objects. Scripts still
+// treat those like the primitive as property keys and in arithmetic
+impl From<JsValue> for Value {
+    fn from(value: JsValue) -> Self {
+        Self::try_from(value.clone()).unwrap_or_else(|_| {
+            let object = ctx()
+                .globals()
+                .get::<_, rquickjs::Function<'static>>("Object")
+                .and_then(|object| object.call::<_, rquickjs::Object<'static>>((value,)))
+                .unwrap();
+            Self::Object(Object::from(object))
+        })
+    }
+}
+
+impl From<Value> for JsValue {
+    fn from(value: Value) -> Self {
+        let ctx = ctx();
+        match value {
+            Value::Undefined => JsValue::new_undefined(ctx),
+            Value::Null => JsValue::new_null(ctx),
+            Value::Boolean(value) => JsValue::new_bool(ctx, value),
+            Value::Number(value) => JsValue::new_number(ctx, value),
+            Value::String(value) => rquickjs::String::from_str(ctx, &value)
+                .unwrap()
+                .into_value(),
+            Value::Array(value) => value.array.into_value(),
+            Value::Object(value) => value.object.into_value(),
+            Value::Function(value) => value.function.into_value(),
+        }
+    }
+}
+
+impl From<rquickjs::Object<'static>> for Object {
+    fn from(object: rquickjs::Object<'static>) -> Self {
+        Self { object }
+    }
+}
+
+impl From<rquickjs::Array<'static>> for Array {
+    fn from(array: rquickjs::Array<'static>) -> Self {
+        Self { array }
+    }
+}
+
+impl From<rquickjs::Function<'static>> for Function {
+    fn from(function: rquickjs::Function<'static>) -> Self {
+        Self { function }
+    }
+}
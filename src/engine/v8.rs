@@ -0,0 +1,514 @@
+use crate::{
+    engine::{Contexts, Engine, HostFunction},
+    value::catch_panic,
+    Args, Array, Function, FunctionBuilder, Object, ScriptLocation, Value,
+};
+
+pub(crate) struct V8;
+
+// pops the callback's scope when it returns, also when it unwinds
+struct CallbackScope;
+
+impl Drop for CallbackScope {
+    fn drop(&mut self) {
+        crate::v8::pop_scope();
+    }
+}
+
+impl Engine for V8 {
+    type Object = v8::Global<v8::Object>;
+    type Array = v8::Global<v8::Array>;
+    type Function = v8::Global<v8::Function>;
+
+    fn eval(source: &str, filename: Option<&str>) -> Result<Value, Value> {
+        let scope = crate::v8::scope();
+        let code = v8::String::new(scope, source).unwrap();
+        let origin = filename.map(|filename| {
+            let name = v8::String::new(scope, filename).unwrap();
+            let source_map_url = v8::String::empty(scope);
+            v8::ScriptOrigin::new(
+                scope,
+                name.into(),
+                0,
+                0,
+                false,
+                0,
+                source_map_url.into(),
+                false,
+                false,
+                false,
+            )
+        });
+
+        let result = {
+            // a syntax error is thrown by `compile`, so it is caught like any other exception
+            let scope = &mut v8::TryCatch::new(scope);
+            crate::v8::push_scope(scope);
+            let script = v8::Script::compile(scope, code, origin.as_ref());
+            if let Some(ret) = script.and_then(|script| script.run(scope)) {
+                Value::try_from(ret)
+            } else {
+                // TODO: don't unwrap
+                let exception = scope.exception().unwrap();
+                Err(Value::from(exception))
+            }
+        };
+        crate::v8::pop_scope();
+        result
+    }
+
+    fn flush() {
+        crate::v8::flush();
+    }
+
+    fn global_object() -> Self::Object {
+        let scope = crate::v8::scope();
+        let context = scope.get_current_context();
+        let global = context.global(scope);
+        v8::Global::new(scope, global)
+    }
+
+    fn object_new() -> Self::Object {
+        let scope = crate::v8::scope();
+        let object = v8::Object::new(scope);
+        v8::Global::new(scope, object)
+    }
+
+    fn object_get(object: &Self::Object, key: &str) -> Value {
+        let scope = crate::v8::scope();
+        let object = v8::Local::new(scope, object);
+        let name = v8::String::new(scope, key).unwrap();
+        let value = object.get(scope, name.into());
+        if let Some(value) = value {
+            Value::from(value)
+        } else {
+            Value::Undefined
+        }
+    }
+
+    fn object_set(object: &Self::Object, key: &str, value: Value) {
+        let scope = crate::v8::scope();
+        let object = v8::Local::new(scope, object);
+        let key = v8::String::new(scope, key).unwrap();
+        let value = v8::Local::<v8::Value>::from(value);
+        object.set(scope, key.into(), value).unwrap();
+    }
+
+    fn object_delete(object: &Self::Object, key: &str) {
+        let scope = crate::v8::scope();
+        let object = v8::Local::new(scope, object);
+        let key = v8::String::new(scope, key).unwrap();
+        object.delete(scope, key.into());
+    }
+
+    fn object_keys(object: &Self::Object) -> Vec<String> {
+        let scope = crate::v8::scope();
+        let object = v8::Local::new(scope, object);
+        let names = object
+            .get_own_property_names(scope, v8::GetPropertyNamesArgs::default())
+            .unwrap();
+        let mut keys = vec![];
+        for i in 0..names.length() {
+            let i_key = v8::Number::new(scope, i as f64);
+            if let Some(name) = names.get(scope, i_key.into()) {
+                let name = Value::from(name);
+                if let Some(name) = name.into_string() {
+                    keys.push(name);
+                }
+            }
+        }
+        keys
+    }
+
+    fn object_prototype(object: &Self::Object) -> Value {
+        let scope = crate::v8::scope();
+        let object = v8::Local::new(scope, object);
+        if let Some(prototype) = object.get_prototype(scope) {
+            Value::from(prototype)
+        } else {
+            Value::Undefined
+        }
+    }
+
+    fn object_set_prototype(object: &Self::Object, prototype: Value) {
+        let scope = crate::v8::scope();
+        let object = v8::Local::new(scope, object);
+        let value = v8::Local::<v8::Value>::from(prototype);
+        object.set_prototype(scope, value);
+    }
+
+    fn object_define_property(object: &Self::Object, key: &str, value: Value) {
+        let scope = crate::v8::scope();
+        let object = v8::Local::new(scope, object);
+        let key = v8::String::new(scope, key).unwrap();
+        let value = v8::Local::<v8::Value>::from(value);
+        let property_descriptor = v8::PropertyDescriptor::new_from_value(value);
+        object.define_property(scope, key.into(), &property_descriptor);
+    }
+
+    fn global_define(key: &str, value: Value) -> Result<(), Value> {
+        let scope = crate::v8::scope();
+        let context = scope.get_current_context();
+        let global = context.global(scope);
+        let name = v8::String::new(scope, key).unwrap();
+        let value = v8::Local::<v8::Value>::from(value);
+        // spelled out, otherwise an existing property keeps its attributes
+        let mut property_descriptor = v8::PropertyDescriptor::new_from_value_writable(value, false);
+        property_descriptor.set_enumerable(false);
+        property_descriptor.set_configurable(false);
+        let result = {
+            let scope = &mut v8::TryCatch::new(scope);
+            crate::v8::push_scope(scope);
+            match global.define_property(scope, name.into(), &property_descriptor) {
+                Some(true) => Ok(()),
+                Some(false) => {
+                    let message =
+                        v8::String::new(scope, &format!("Cannot redefine property: {}", key))
+                            .unwrap();
+                    Err(Value::from(v8::Exception::type_error(scope, message)))
+                }
+                None => Err(Value::from(scope.exception().unwrap())),
+            }
+        };
+        crate::v8::pop_scope();
+        result
+    }
+
+    fn array_new(length: u32) -> Self::Array {
+        let scope = crate::v8::scope();
+        let array = v8::Array::new(scope, length as i32);
+        v8::Global::new(scope, array)
+    }
+
+    fn array_length(array: &Self::Array) -> u32 {
+        let scope = crate::v8::scope();
+        let array = v8::Local::new(scope, array);
+        array.length()
+    }
+
+    fn array_get(array: &Self::Array, index: u32) -> Value {
+        let scope = crate::v8::scope();
+        let array = v8::Local::new(scope, array);
+        let key = v8::Number::new(scope, index as f64);
+        if let Some(value) = array.get(scope, key.into()) {
+            Value::from(value)
+        } else {
+            Value::Undefined
+        }
+    }
+
+    fn array_set(array: &Self::Array, index: u32, value: Value) {
+        let scope = crate::v8::scope();
+        let array = v8::Local::new(scope, array);
+        let key = v8::Number::new(scope, index as f64);
+        let value = v8::Local::<v8::Value>::from(value);
+        array.set(scope, key.into(), value);
+    }
+
+    fn array_as_object(array: &Self::Array) -> Self::Object {
+        let scope = crate::v8::scope();
+        let local = v8::Local::new(scope, array);
+        v8::Global::new(scope, v8::Local::<v8::Object>::from(local))
+    }
+
+    fn function_new(builder: FunctionBuilder, body: HostFunction) -> Self::Function {
+        let scope = crate::v8::scope();
+        let data_arr = Array::new();
+        data_arr.push(body as usize as f64);
+        data_arr.push(builder.data);
+        let function = v8::Function::builder(
+            |v8_scope: &mut v8::HandleScope<'_>,
+             v8_args: v8::FunctionCallbackArguments<'_>,
+             mut v8_ret: v8::ReturnValue<'_>| {
+                crate::v8::push_scope(v8_scope);
+                let _scope = CallbackScope;
+                // converting the arguments can fail or panic as well, nothing may unwind into V8
+                let result = catch_panic(|| {
+                    let data_arr = Value::from(v8_args.data()).into_array().unwrap();
+                    let body_ptr = data_arr.get(0).into_number().unwrap();
+                    let f: HostFunction = unsafe { std::mem::transmute(body_ptr as usize) };
+                    let data = data_arr.get(1);
+                    let this = Value::from(Object::from(v8_args.this()));
+                    let holder = Value::from(Object::from(v8_args.holder()));
+                    let new_target = Value::try_from(v8_args.new_target())?;
+                    let mut args = Args {
+                        this,
+                        holder,
+                        new_target,
+                        data,
+                        args: vec![],
+                    };
+                    for i in 0..v8_args.length() {
+                        args.args.push(Value::try_from(v8_args.get(i))?);
+                    }
+                    f(args)
+                });
+                match result {
+                    Ok(value) => v8_ret.set(v8::Local::<v8::Value>::from(value)),
+                    Err(err) => {
+                        v8_scope.throw_exception(v8::Local::<v8::Value>::from(err));
+                    }
+                }
+            },
+        )
+        .data(v8::Local::<v8::Value>::from(v8::Local::<v8::Array>::from(
+            data_arr,
+        )))
+        .length(builder.length as i32)
+        .constructor_behavior(if builder.constructor {
+            v8::ConstructorBehavior::Allow
+        } else {
+            v8::ConstructorBehavior::Throw
+        })
+        .side_effect_type(if builder.side_effect_free {
+            v8::SideEffectType::HasNoSideEffect
+        } else {
+            v8::SideEffectType::HasSideEffect
+        })
+        .build(scope)
+        .unwrap();
+        let name = v8::String::new(scope, &builder.name).unwrap();
+        function.set_name(name);
+        v8::Global::new(scope, function)
+    }
+
+    fn function_call(
+        function: &Self::Function,
+        receiver: Value,
+        args: Vec<Value>,
+    ) -> Result<Value, Value> {
+        let scope = crate::v8::scope();
+        let function = v8::Local::new(scope, function);
+        let receiver = v8::Local::<v8::Value>::from(receiver);
+        let args = args
+            .into_iter()
+            .map(v8::Local::<v8::Value>::from)
+            .collect::<Vec<_>>();
+        let result = {
+            let scope = &mut v8::TryCatch::new(scope);
+            crate::v8::push_scope(scope);
+            if let Some(ret) = function.call(scope, receiver, &args) {
+                Value::try_from(ret)
+            } else {
+                // TODO: don't unwrap
+                let exception = scope.exception().unwrap();
+                Err(Value::from(exception))
+            }
+        };
+        crate::v8::pop_scope();
+        result
+    }
+
+    fn function_new_instance(function: &Self::Function, args: Vec<Value>) -> Result<Object, Value> {
+        let scope = crate::v8::scope();
+        let function = v8::Local::new(scope, function);
+        let args = args
+            .into_iter()
+            .map(v8::Local::<v8::Value>::from)
+            .collect::<Vec<_>>();
+        let result = {
+            let scope = &mut v8::TryCatch::new(scope);
+            crate::v8::push_scope(scope);
+            if let Some(ret) = function.new_instance(scope, &args) {
+                Ok(Object::from(ret))
+            } else {
+                // TODO: don't unwrap
+                let exception = scope.exception().unwrap();
+                Err(Value::from(exception))
+            }
+        };
+        crate::v8::pop_scope();
+        result
+    }
+
+    fn function_name(function: &Self::Function) -> String {
+        let scope = crate::v8::scope();
+        let function = v8::Local::new(scope, function);
+        function.get_name(scope).to_rust_string_lossy(scope)
+    }
+
+    fn function_is_async(function: &Self::Function) -> bool {
+        let scope = crate::v8::scope();
+        let function = v8::Local::new(scope, function);
+        function.is_async_function()
+    }
+
+    fn function_is_generator(function: &Self::Function) -> bool {
+        let scope = crate::v8::scope();
+        let function = v8::Local::new(scope, function);
+        function.is_generator_function()
+    }
+
+    fn function_script_location(function: &Self::Function) -> Option<ScriptLocation> {
+        let scope = crate::v8::scope();
+        let function = v8::Local::new(scope, function);
+        let line = function.get_script_line_number()?;
+        let column = function.get_script_column_number()?;
+        let file = function
+            .get_script_origin()
+            .resource_name()
+            .map(Value::from)
+            .and_then(|name| name.into_string())
+            .filter(|name| !name.is_empty());
+        Some(ScriptLocation {
+            file,
+            line: line + 1,
+            column: column + 1,
+        })
+    }
+
+    fn function_as_object(function: &Self::Function) -> Self::Object {
+        let scope = crate::v8::scope();
+        let local = v8::Local::new(scope, function);
+        v8::Global::new(scope, v8::Local::<v8::Object>::from(local))
+    }
+}
+
+impl Contexts for V8 {
+    type Context = v8::Global<v8::Context>;
+
+    fn context_new() -> Self::Context {
+        let scope = crate::v8::scope();
+        let context = v8::Context::new(scope);
+        // contexts with the same security token can access each other's objects
+        let token = scope.get_current_context().get_security_token(scope);
+        context.set_security_token(token);
+        v8::Global::new(scope, context)
+    }
+
+    fn context_enter(context: &Self::Context) {
+        crate::v8::enter_context(context);
+    }
+
+    fn context_leave() {
+        crate::v8::leave_context();
+    }
+
+    fn context_set_code_generation(context: &Self::Context, allowed: bool) -> bool {
+        let scope = crate::v8::scope();
+        let context = v8::Local::new(scope, context);
+        context.set_allow_generation_from_strings(allowed);
+        true
+    }
+}
+
+// symbols and BigInts have no variant, handing one to Rust where that can fail is a TypeError
+impl TryFrom<v8::Local<'_, v8::Value>> for Value {
+    type Error = Value;
+
+    fn try_from(value: v8::Local<v8::Value>) -> Result<Self, Value> {
+        let scope = crate::v8::scope();
+        if value.is_undefined() {
+            Ok(Self::Undefined)
+        } else if value.is_null() {
+            Ok(Self::Null)
+        } else if value.is_boolean() {
+            Ok(Self::Boolean(value.boolean_value(scope)))
+        } else if value.is_number() {
+            Ok(Self::Number(value.number_value(scope).unwrap()))
+        } else if value.is_string() {
+            let string: v8::Local<v8::String> = value.try_into().unwrap();
+            Ok(Self::String(string.to_rust_string_lossy(scope)))
+        } else if value.is_function() {
+            Ok(Self::Function(Function::from(
+                v8::Local::<v8::Function>::try_from(value).unwrap(),
+            )))
+        } else if value.is_array() {
+            // TODO: remove unwrap?
+            Ok(Self::Array(Array::from(
+                v8::Local::<v8::Array>::try_from(value).unwrap(),
+            )))
+        } else if value.is_object() {
+            // TODO: remove unwrap?
+            Ok(Self::Object(Object::from(
+                v8::Local::<v8::Object>::try_from(value).unwrap(),
+            )))
+        } else {
+            let type_of = value.type_of(scope).to_rust_string_lossy(scope);
+            let message =
+                v8::String::new(scope, &format!("a {} can't be passed to Rust", type_of)).unwrap();
+            Err(Value::from(v8::Exception::type_error(scope, message)))
+        }
+    }
+}
+
+// where the conversion can't fail, symbols and BigInts become their wrapper objects. Scripts still
+// treat those like the primitive as property keys and in arithmetic
+impl From<v8::Local<'_, v8::Value>> for Value {
+    fn from(value: v8::Local<v8::Value>) -> Self {
+        Self::try_from(value).unwrap_or_else(|_| {
+            let scope = crate::v8::scope();
+            Self::Object(Object::from(value.to_object(scope).unwrap()))
+        })
+    }
+}
+
+impl From<Value> for v8::Local<'_, v8::Value> {
+    fn from(value: Value) -> Self {
+        let scope = crate::v8::scope();
+        match value {
+            Value::Undefined => v8::undefined(scope).into(),
+            Value::Null => v8::null(scope).into(),
+            Value::Boolean(value) => v8::Boolean::new(scope, value).into(),
+            Value::Number(value) => v8::Number::new(scope, value).into(),
+            Value::String(value) => v8::String::new(scope, value.as_str()).unwrap().into(),
+            Value::Array(value) => {
+                v8::Local::<v8::Value>::from(v8::Local::<v8::Array>::from(value))
+            }
+            Value::Object(value) => {
+                v8::Local::<v8::Value>::from(v8::Local::<v8::Object>::from(value))
+            }
+            Value::Function(value) => {
+                v8::Local::<v8::Value>::from(v8::Local::<v8::Function>::from(value))
+            }
+        }
+    }
+}
+
+impl From<v8::Local<'_, v8::Object>> for Object {
+    fn from(value: v8::Local<v8::Object>) -> Self {
+        let scope = crate::v8::scope();
+        Self {
+            object: v8::Global::new(scope, value),
+        }
+    }
+}
+
+impl From<Object> for v8::Local<'_, v8::Object> {
+    fn from(value: Object) -> Self {
+        let scope = crate::v8::scope();
+        v8::Local::new(scope, &value.object)
+    }
+}
+
+impl From<v8::Local<'_, v8::Array>> for Array {
+    fn from(value: v8::Local<v8::Array>) -> Self {
+        let scope = crate::v8::scope();
+        Self {
+            array: v8::Global::new(scope, value),
+        }
+    }
+}
+
+impl From<Array> for v8::Local<'_, v8::Array> {
+    fn from(value: Array) -> Self {
+        let scope = crate::v8::scope();
+        v8::Local::new(scope, &value.array)
+    }
+}
+
+impl From<v8::Local<'_, v8::Function>> for Function {
+    fn from(value: v8::Local<v8::Function>) -> Self {
+        let scope = crate::v8::scope();
+        Self {
+            function: v8::Global::new(scope, value),
+        }
+    }
+}
+
+impl From<Function> for v8::Local<'_, v8::Function> {
+    fn from(value: Function) -> Self {
+        let scope = crate::v8::scope();
+        v8::Local::new(scope, &value.function)
+    }
+}
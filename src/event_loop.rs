@@ -0,0 +1,563 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    future::Future,
+    pin::Pin,
+    sync::{Arc, Mutex},
+    task::{Context, Wake, Waker},
+    thread::Thread,
+    time::{Duration, Instant},
+};
+
+use crate::{eval, global_set, thread_state::ThreadState, Function, OrThrow, Value};
+
+thread_state! {
+    static EVENT_LOOP: Option<EventLoop> = None;
+}
+
+enum Clock {
+    Real(Instant),
+    Virtual(Duration),
+}
+
+struct Timer {
+    deadline: Duration,
+    interval: Option<Duration>,
+    callback: Value,
+    args: Vec<Value>,
+}
+
+type Task = Pin<Box<dyn Future<Output = ()>>>;
+
+// wakers can be sent to other threads, so woken tasks are queued behind a mutex
+struct TaskWaker {
+    id: u32,
+    woken: Arc<Mutex<Vec<u32>>>,
+    thread: Thread,
+}
+
+impl Wake for TaskWaker {
+    fn wake(self: Arc<Self>) {
+        self.wake_by_ref();
+    }
+
+    fn wake_by_ref(self: &Arc<Self>) {
+        self.woken.lock().unwrap().push(self.id);
+        self.thread.unpark();
+    }
+}
+
+enum Message {
+    Call(u32, Box<dyn FnOnce() -> Vec<Value> + Send>),
+    Release(u32),
+}
+
+struct EventLoop {
+    clock: Clock,
+    timers: BTreeMap<u32, Timer>,
+    next_id: u32,
+    tasks: HashMap<u32, Task>,
+    woken: Arc<Mutex<Vec<u32>>>,
+    next_task_id: u32,
+    messages: Arc<Mutex<Vec<Message>>>,
+    threadsafe_functions: HashMap<u32, Function>,
+    next_threadsafe_id: u32,
+}
+
+impl Default for EventLoop {
+    fn default() -> Self {
+        Self {
+            clock: Clock::Real(Instant::now()),
+            timers: BTreeMap::new(),
+            next_id: 1,
+            tasks: HashMap::new(),
+            woken: Arc::new(Mutex::new(Vec::new())),
+            next_task_id: 1,
+            messages: Arc::new(Mutex::new(Vec::new())),
+            threadsafe_functions: HashMap::new(),
+            next_threadsafe_id: 1,
+        }
+    }
+}
+
+fn event_loop() -> &'static mut EventLoop {
+    unsafe { EVENT_LOOP.get().get_or_insert_with(EventLoop::default) }
+}
+
+pub(crate) fn install() {
+    event_loop().timers.clear();
+    event_loop().tasks.clear();
+    global_set(
+        "setTimeout",
+        Function::new(|args| Ok(add_timer(&args, false))),
+    );
+    global_set(
+        "setInterval",
+        Function::new(|args| Ok(add_timer(&args, true))),
+    );
+    global_set(
+        "clearTimeout",
+        Function::new(|args| {
+            clear_timer(args.get(0));
+            Ok(())
+        }),
+    );
+    global_set(
+        "clearInterval",
+        Function::new(|args| {
+            clear_timer(args.get(0));
+            Ok(())
+        }),
+    );
+    global_set(
+        "queueMicrotask",
+        Function::new(|args| {
+            let callback = args
+                .get(0)
+                .into_function()
+                .or_throw("The callback must be a function.")?;
+            let scope = crate::v8::scope();
+            scope.enqueue_microtask(v8::Local::<v8::Function>::from(callback));
+            Ok(())
+        }),
+    );
+}
+
+fn add_timer(args: &crate::Args, repeat: bool) -> f64 {
+    let delay = args.get(1).into_number().unwrap_or(0.);
+    // like Node, delays that don't fit in a signed 32-bit number of milliseconds, infinity included,
+    // become a single millisecond
+    let delay = if delay > i32::MAX as f64 {
+        Duration::from_millis(1)
+    } else if delay > 0. {
+        Duration::from_secs_f64(delay / 1000.)
+    } else {
+        Duration::ZERO
+    };
+    let event_loop = event_loop();
+    let id = event_loop.next_id;
+    event_loop.next_id += 1;
+    let timer = Timer {
+        deadline: now().saturating_add(delay),
+        // like HTML timers, intervals repeat at most once per millisecond so the clock moves on
+        interval: repeat.then(|| delay.max(Duration::from_millis(1))),
+        callback: args.get(0),
+        args: (2..args.length()).map(|i| args.get(i)).collect(),
+    };
+    event_loop.timers.insert(id, timer);
+    id as f64
+}
+
+fn clear_timer(id: Value) {
+    if let Some(id) = id.into_number() {
+        event_loop().timers.remove(&(id as u32));
+    }
+}
+
+fn next_deadline() -> Option<Duration> {
+    event_loop()
+        .timers
+        .values()
+        .map(|timer| timer.deadline)
+        .min()
+}
+
+fn run_microtasks() {
+    loop {
+        crate::v8::pump_platform();
+        let scope = crate::v8::scope();
+        scope.perform_microtask_checkpoint();
+        // polled tasks can settle promises, which queues more microtasks
+        if !poll_tasks() {
+            break;
+        }
+    }
+}
+
+fn poll_tasks() -> bool {
+    let woken = std::mem::take(&mut *event_loop().woken.lock().unwrap());
+    let polled = !woken.is_empty();
+    for id in woken {
+        // the task is taken out while polled so it can spawn more tasks
+        let Some(mut task) = event_loop().tasks.remove(&id) else {
+            continue;
+        };
+        let waker = Waker::from(Arc::new(TaskWaker {
+            id,
+            woken: event_loop().woken.clone(),
+            thread: std::thread::current(),
+        }));
+        if task
+            .as_mut()
+            .poll(&mut Context::from_waker(&waker))
+            .is_pending()
+        {
+            event_loop().tasks.insert(id, task);
+        }
+    }
+    polled
+}
+
+// the part of a `ThreadsafeFunction` its clones share, the target is released with the last one
+struct ThreadsafeTarget {
+    id: u32,
+    messages: Arc<Mutex<Vec<Message>>>,
+    thread: Thread,
+}
+
+impl ThreadsafeTarget {
+    fn send(&self, message: Message) {
+        self.messages.lock().unwrap().push(message);
+        self.thread.unpark();
+    }
+}
+
+impl Drop for ThreadsafeTarget {
+    fn drop(&mut self) {
+        self.send(Message::Release(self.id));
+    }
+}
+
+// calls a JS function from any thread. Calls are queued with their `Send` data and run on the
+// event loop thread during the next tick, in the order they were made
+pub struct ThreadsafeFunction<T> {
+    target: Arc<ThreadsafeTarget>,
+    to_args: fn(T) -> Vec<Value>,
+}
+
+impl<T: Send + 'static> ThreadsafeFunction<T> {
+    // has to be created on the event loop thread, `to_args` runs there and turns the data of each
+    // call into its arguments
+    pub fn new(function: Function, to_args: fn(T) -> Vec<Value>) -> Self {
+        let event_loop = event_loop();
+        let id = event_loop.next_threadsafe_id;
+        event_loop.next_threadsafe_id += 1;
+        event_loop.threadsafe_functions.insert(id, function);
+        let target = ThreadsafeTarget {
+            id,
+            messages: event_loop.messages.clone(),
+            thread: std::thread::current(),
+        };
+        Self {
+            target: Arc::new(target),
+            to_args,
+        }
+    }
+
+    pub fn call(&self, data: T) {
+        let to_args = self.to_args;
+        let args = Box::new(move || to_args(data));
+        self.target.send(Message::Call(self.target.id, args));
+    }
+}
+
+impl<T> Clone for ThreadsafeFunction<T> {
+    fn clone(&self) -> Self {
+        Self {
+            target: self.target.clone(),
+            to_args: self.to_args,
+        }
+    }
+}
+
+fn run_threadsafe_calls() -> Result<(), Value> {
+    let messages = std::mem::take(&mut *event_loop().messages.lock().unwrap());
+    let mut messages = messages.into_iter();
+    while let Some(message) = messages.next() {
+        match message {
+            Message::Call(id, args) => {
+                let Some(function) = event_loop().threadsafe_functions.get(&id).cloned() else {
+                    continue;
+                };
+                if let Err(err) = function.call(args()) {
+                    // the calls after the one that threw run on the next tick
+                    let mut queue = event_loop().messages.lock().unwrap();
+                    queue.splice(0..0, messages);
+                    return Err(err);
+                }
+                run_microtasks();
+            }
+            Message::Release(id) => {
+                event_loop().threadsafe_functions.remove(&id);
+            }
+        }
+    }
+    Ok(())
+}
+
+// runs the future on the event loop thread, it is first polled on the next tick. Like unsettled
+// promises, tasks waiting to be woken don't keep the loop alive on their own
+pub fn spawn_local(future: impl Future<Output = ()> + 'static) {
+    let event_loop = event_loop();
+    let id = event_loop.next_task_id;
+    event_loop.next_task_id += 1;
+    event_loop.tasks.insert(id, Box::pin(future));
+    event_loop.woken.lock().unwrap().push(id);
+}
+
+// drives the event loop until the future completes, parking the thread while only tasks woken
+// from other threads could make progress
+pub fn block_on<T: 'static>(future: impl Future<Output = T> + 'static) -> Result<T, Value> {
+    let output = std::rc::Rc::new(std::cell::RefCell::new(None));
+    let slot = output.clone();
+    spawn_local(async move {
+        *slot.borrow_mut() = Some(future.await);
+    });
+    loop {
+        tick()?;
+        if let Some(output) = output.borrow_mut().take() {
+            return Ok(output);
+        }
+        wait();
+    }
+}
+
+// parks the thread until the next timer is due or another thread wakes a task or queues a call,
+// with virtual time the clock jumps to the next timer instead
+pub(crate) fn wait() {
+    if !event_loop().woken.lock().unwrap().is_empty()
+        || !event_loop().messages.lock().unwrap().is_empty()
+    {
+        return;
+    }
+    match (next_deadline(), &event_loop().clock) {
+        (Some(deadline), Clock::Real(..)) => {
+            std::thread::park_timeout(deadline.saturating_sub(now()))
+        }
+        (Some(deadline), Clock::Virtual(..)) => advance(deadline.saturating_sub(now())),
+        (None, _) => std::thread::park(),
+    }
+}
+
+pub fn now() -> Duration {
+    match event_loop().clock {
+        Clock::Real(start) => start.elapsed(),
+        Clock::Virtual(now) => now,
+    }
+}
+
+pub fn use_virtual_time() {
+    event_loop().clock = Clock::Virtual(now());
+}
+
+pub fn use_real_time() {
+    let now = now();
+    event_loop().clock = Clock::Real(Instant::now() - now);
+}
+
+pub fn advance(duration: Duration) {
+    match &mut event_loop().clock {
+        Clock::Real(..) => std::thread::sleep(duration),
+        Clock::Virtual(now) => *now += duration,
+    }
+}
+
+// live `ThreadsafeFunction`s don't count, only calls they already queued
+pub fn has_pending() -> bool {
+    !event_loop().timers.is_empty()
+        || !event_loop().woken.lock().unwrap().is_empty()
+        || !event_loop().messages.lock().unwrap().is_empty()
+}
+
+pub fn tick() -> Result<bool, Value> {
+    #[cfg(feature = "inspector")]
+    crate::inspector::poll();
+    run_microtasks();
+    run_threadsafe_calls()?;
+    let now = now();
+    let mut due = event_loop()
+        .timers
+        .iter()
+        .filter(|(_, timer)| timer.deadline <= now)
+        .map(|(id, timer)| (timer.deadline, *id))
+        .collect::<Vec<_>>();
+    due.sort();
+    for (_, id) in due {
+        let event_loop = event_loop();
+        let Some(timer) = event_loop.timers.remove(&id) else {
+            continue;
+        };
+        let callback = timer.callback.clone();
+        let args = timer.args.clone();
+        if let Some(interval) = timer.interval {
+            event_loop.timers.insert(
+                id,
+                Timer {
+                    deadline: now.saturating_add(interval),
+                    ..timer
+                },
+            );
+        }
+        match callback {
+            Value::Function(function) => {
+                function.call(args)?;
+            }
+            Value::String(source) => {
+                eval(source)?;
+            }
+            _ => {}
+        }
+        run_microtasks();
+    }
+    Ok(has_pending())
+}
+
+pub fn run_until_idle() -> Result<(), Value> {
+    while tick()? {
+        if let Some(deadline) = next_deadline() {
+            advance(deadline.saturating_sub(now()));
+        }
+    }
+    Ok(())
+}
+
+pub fn run_for(duration: Duration) -> Result<(), Value> {
+    let end = now() + duration;
+    loop {
+        tick()?;
+        let now = now();
+        if now >= end {
+            break;
+        }
+        let until = next_deadline().unwrap_or(end).min(end);
+        advance(until.saturating_sub(now));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::Duration;
+
+    use crate::{eval, event_loop, event_loop::ThreadsafeFunction, Value};
+
+    #[test]
+    fn set_timeout() {
+        event_loop::use_virtual_time();
+        let start = event_loop::now();
+        eval(
+            "globalThis.fired = [];
+            setTimeout(() => fired.push('a'), 100);
+            setTimeout((x) => fired.push(x), 50, 'b');",
+        )
+        .unwrap();
+        event_loop::run_until_idle().unwrap();
+        assert_eq!(event_loop::now() - start, Duration::from_millis(100));
+        assert_eq!(eval("fired.join()").unwrap(), Value::from("b,a"));
+    }
+
+    #[test]
+    fn clear_timeout() {
+        event_loop::use_virtual_time();
+        eval(
+            "globalThis.fired = false;
+            clearTimeout(setTimeout(() => fired = true, 10));",
+        )
+        .unwrap();
+        event_loop::run_until_idle().unwrap();
+        assert_eq!(eval("fired").unwrap(), Value::Boolean(false));
+    }
+
+    #[test]
+    fn set_interval() {
+        event_loop::use_virtual_time();
+        eval(
+            "globalThis.count = 0;
+            globalThis.interval = setInterval(() => count++, 10);",
+        )
+        .unwrap();
+        event_loop::run_for(Duration::from_millis(35)).unwrap();
+        assert_eq!(eval("count").unwrap(), Value::Number(3.));
+        eval("clearInterval(interval)").unwrap();
+        assert!(!event_loop::has_pending());
+    }
+
+    #[test]
+    fn zero_interval() {
+        event_loop::use_virtual_time();
+        eval(
+            "globalThis.zeroCount = 0;
+            globalThis.zeroInterval = setInterval(() => zeroCount++, 0);",
+        )
+        .unwrap();
+        event_loop::run_for(Duration::from_millis(5)).unwrap();
+        assert_eq!(eval("zeroCount").unwrap(), Value::Number(6.));
+        eval("clearInterval(zeroInterval)").unwrap();
+        assert!(!event_loop::has_pending());
+    }
+
+    #[test]
+    fn huge_delays() {
+        event_loop::use_virtual_time();
+        let start = event_loop::now();
+        eval(
+            "globalThis.hugeFired = [];
+            setTimeout(() => hugeFired.push('max'), 2 ** 31 - 1);
+            setTimeout(() => hugeFired.push('huge'), 1e300);
+            setTimeout(() => hugeFired.push('infinite'), Infinity);",
+        )
+        .unwrap();
+        event_loop::run_for(Duration::from_millis(1)).unwrap();
+        assert_eq!(
+            eval("hugeFired.join()").unwrap(),
+            Value::from("huge,infinite")
+        );
+        assert_eq!(event_loop::now() - start, Duration::from_millis(1));
+        event_loop::run_until_idle().unwrap();
+        assert_eq!(
+            event_loop::now() - start,
+            Duration::from_millis(i32::MAX as u64)
+        );
+    }
+
+    #[test]
+    fn queue_microtask() {
+        eval(
+            "globalThis.order = [];
+            setTimeout(() => order.push('timeout'), 0);
+            queueMicrotask(() => order.push('microtask'));
+            order.push('sync');",
+        )
+        .unwrap();
+        event_loop::run_until_idle().unwrap();
+        assert_eq!(
+            eval("order.join()").unwrap(),
+            Value::from("sync,microtask,timeout")
+        );
+    }
+
+    #[test]
+    fn timer_exception() {
+        event_loop::use_virtual_time();
+        eval("setTimeout(() => { throw 'nope'; }, 10)").unwrap();
+        assert_eq!(
+            event_loop::run_until_idle().unwrap_err(),
+            Value::from("nope")
+        );
+    }
+
+    #[test]
+    fn threadsafe_function() {
+        eval("globalThis.loaded = [];").unwrap();
+        let function = eval("(name, size) => loaded.push(`${name}:${size}`)")
+            .unwrap()
+            .into_function()
+            .unwrap();
+        let function = ThreadsafeFunction::new(function, |(name, size): (String, u32)| {
+            vec![name.into(), (size as f64).into()]
+        });
+        let worker = function.clone();
+        std::thread::spawn(move || {
+            worker.call(("a.png".to_owned(), 3));
+            worker.call(("b.png".to_owned(), 5));
+        })
+        .join()
+        .unwrap();
+        assert!(event_loop::has_pending());
+        drop(function);
+        event_loop::run_until_idle().unwrap();
+        assert_eq!(
+            eval("loaded.join()").unwrap(),
+            Value::from("a.png:3,b.png:5")
+        );
+    }
+}
@@ -1,4 +1,10 @@
-use crate::Value;
+use std::{any::Any, error::Error, fmt::Display};
+
+use crate::{value::helper, Value};
+
+const ERROR: &str = "(function (message, cause) {
+    return cause === undefined ? new Error(message) : new Error(message, { cause });
+})";
 
 // TODO: should probably be represented as the javascript Error type instead?
 pub struct Exception(Value);
@@ -8,17 +14,45 @@ impl Exception {
         // TODO: instantiate Error type
         Self(Value::String(message.as_ref().to_owned()))
     }
-}
 
-impl From<&str> for Exception {
-    fn from(value: &str) -> Self {
-        Exception::msg(value)
+    // a JS Error with the error's message, its `source()` chain becomes the `cause` chain
+    pub fn from_error(error: &(dyn Error + 'static)) -> Self {
+        let cause = error
+            .source()
+            .map(|source| Self::from_error(source).0)
+            .unwrap_or(Value::Undefined);
+        Self(error_object(error.to_string(), cause))
+    }
+
+    // wraps the exception in a JS Error with the given message, keeping it as the `cause`
+    pub fn context(self, context: impl Display) -> Self {
+        Self(error_object(context.to_string(), self.0))
+    }
+
+    pub(crate) fn from_panic(payload: Box<dyn Any + Send>) -> Self {
+        let message = if let Some(message) = payload.downcast_ref::<&str>() {
+            message
+        } else if let Some(message) = payload.downcast_ref::<String>() {
+            message.as_str()
+        } else {
+            "Box<dyn Any>"
+        };
+        Self(error_object(
+            format!("Rust panic: {}", message),
+            Value::Undefined,
+        ))
     }
 }
 
-impl From<String> for Exception {
-    fn from(value: String) -> Self {
-        Exception::msg(value)
+fn error_object(message: String, cause: Value) -> Value {
+    helper(ERROR).call([message.into(), cause]).unwrap()
+}
+
+// like anyhow, messages go through `Exception::msg` so that any `Error` converts with `?`. Boxed
+// errors and `anyhow::Error` don't implement `Error`, pass them to `Exception::from_error`
+impl<E: Error + 'static> From<E> for Exception {
+    fn from(error: E) -> Self {
+        Exception::from_error(&error)
     }
 }
 
@@ -34,28 +68,176 @@ impl From<Exception> for Value {
     }
 }
 
+// throws `Exception::msg(message)` in place of the `None` or the error
 pub trait OrThrow where Self: Sized {
     type Value;
 
-    fn or_throw(self, exception: impl Into<Exception>) -> Result<Self::Value, Exception>;
+    fn or_throw(self, message: impl Display) -> Result<Self::Value, Exception>;
 }
 
 impl<T> OrThrow for Option<T> {
     type Value = T;
-    fn or_throw(self, exception: impl Into<Exception>) -> Result<Self::Value, Exception> {
+    fn or_throw(self, message: impl Display) -> Result<Self::Value, Exception> {
         match self {
             Some(ok) => Ok(ok),
-            None => Err(exception.into()),
+            None => Err(Exception::msg(message.to_string())),
         }
     }
 }
 
 impl<T, E> OrThrow for Result<T, E> {
     type Value = T;
-    fn or_throw(self, exception: impl Into<Exception>) -> Result<Self::Value, Exception> {
+    fn or_throw(self, message: impl Display) -> Result<Self::Value, Exception> {
         match self {
             Ok(ok) => Ok(ok),
-            Err(_) => Err(exception.into()),
+            Err(_) => Err(Exception::msg(message.to_string())),
+        }
+    }
+}
+
+// like `or_throw`, but the error is kept as the `cause` of the thrown Error
+pub trait ErrorContext: Sized {
+    type Value;
+
+    fn context(self, context: impl Display) -> Result<Self::Value, Exception>;
+}
+
+impl<T> ErrorContext for Option<T> {
+    type Value = T;
+    fn context(self, context: impl Display) -> Result<Self::Value, Exception> {
+        match self {
+            Some(ok) => Ok(ok),
+            None => Err(Exception(error_object(
+                context.to_string(),
+                Value::Undefined,
+            ))),
+        }
+    }
+}
+
+impl<T, E: Error + 'static> ErrorContext for Result<T, E> {
+    type Value = T;
+    fn context(self, context: impl Display) -> Result<Self::Value, Exception> {
+        match self {
+            Ok(ok) => Ok(ok),
+            Err(err) => Err(Exception::from_error(&err).context(context)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    #[cfg(engine = "browser")]
+    use wasm_bindgen_test::wasm_bindgen_test as test;
+
+    use crate::{eval, global_set, ErrorContext, Exception, Function, OrThrow, Value};
+
+    #[derive(Debug)]
+    struct ConfigError(std::num::ParseIntError);
+
+    impl std::fmt::Display for ConfigError {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            write!(f, "invalid config")
+        }
+    }
+
+    impl std::error::Error for ConfigError {
+        fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+            Some(&self.0)
+        }
+    }
+
+    #[test]
+    fn error_conversion() {
+        let parse = Function::new(|args| {
+            let number: i32 = args.get(0).into_string().unwrap_or_default().parse()?;
+            Ok(number as f64)
+        });
+        global_set("parse", parse);
+        assert_eq!(eval("parse('12')").unwrap(), Value::Number(12.));
+        assert_eq!(
+            eval("try { parse('x') } catch (err) { err instanceof Error && err.message }").unwrap(),
+            Value::from("invalid digit found in string")
+        );
+
+        // any `Error` converts, not just the ones the crate knows about
+        let load = Function::new(|_| {
+            Err::<(), _>(ConfigError("x".parse::<i32>().unwrap_err()))?;
+            Ok(())
+        });
+        global_set("load", load);
+        assert_eq!(
+            eval("try { load() } catch (err) { `${err.message}: ${err.cause.message}` }").unwrap(),
+            Value::from("invalid config: invalid digit found in string")
+        );
+    }
+
+    #[test]
+    fn messages() {
+        let check = Function::new(|args| {
+            let value = args.get(0).into_number().or_throw("expected a number")?;
+            if value < 0. {
+                return Err(Exception::msg(format!("{} is negative", value)));
+            }
+            Ok(value)
+        });
+        global_set("checkNumber", check);
+        assert_eq!(
+            eval(
+                "[1, 'x', -1].map((value) => {
+                    try {
+                        return checkNumber(value);
+                    } catch (err) {
+                        return err;
+                    }
+                }).join()"
+            )
+            .unwrap(),
+            Value::from("1,expected a number,-1 is negative")
+        );
+    }
+
+    #[test]
+    fn context() {
+        let read = Function::new(|_| {
+            std::fs::read_to_string("/does/not/exist").context("could not read config")?;
+            Ok(())
+        });
+        global_set("read", read);
+        assert_eq!(
+            eval(
+                "try { read() } catch (err) { err.message + ' / ' + (err.cause instanceof Error) }"
+            )
+            .unwrap(),
+            Value::from("could not read config / true")
+        );
+        let check = Function::new(|_| {
+            Err::<(), _>(ConfigError("x".parse::<i32>().unwrap_err())).context("bad settings")?;
+            Ok(())
+        });
+        global_set("check", check);
+        assert_eq!(
+            eval("try { check() } catch (err) { `${err.message}: ${err.cause.message}` }").unwrap(),
+            Value::from("bad settings: invalid config")
+        );
+        let err = Value::from(None::<()>.context("missing").unwrap_err());
+        assert_eq!(
+            err.as_object().unwrap().get("message"),
+            Value::from("missing")
+        );
+    }
+
+    #[test]
+    fn panics() {
+        #[cfg(engine = "v8")]
+        {
+            let explode = Function::new(|_| -> Result<(), Exception> { panic!("boom") });
+            global_set("explode", explode);
+            assert_eq!(
+                eval("try { explode() } catch (err) { err.message }").unwrap(),
+                Value::from("Rust panic: boom")
+            );
+            assert_eq!(eval("1 + 1").unwrap(), Value::Number(2.));
         }
     }
 }
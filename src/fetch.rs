@@ -0,0 +1,674 @@
+use std::{
+    cell::RefCell,
+    fs::File,
+    io::Read,
+    path::{Component, Path, PathBuf},
+};
+
+use url::Url;
+
+use crate::{
+    eval, global_set, native,
+    thread_state::ThreadState,
+    web::{from_bytes, to_bytes},
+    Array, Exception, Function, Object, OrThrow, Value,
+};
+
+thread_state! {
+    static HANDLER: Option<Box<dyn FetchHandler>> = None;
+}
+
+const GLUE: &str = r#"(function (native) {
+    "use strict";
+
+    function normalizeName(name) {
+        name = String(name).toLowerCase();
+        if (!/^[!#$%&'*+\-.^_`|~0-9a-z]+$/.test(name)) {
+            throw new TypeError(`Invalid header name: ${name}`);
+        }
+        return name;
+    }
+
+    function normalizeValue(value) {
+        return String(value).replace(/^[\t\n\r ]+|[\t\n\r ]+$/g, "");
+    }
+
+    class Headers {
+        #list = [];
+
+        constructor(init = undefined) {
+            if (init instanceof Headers) {
+                init = [...init];
+            }
+            if (typeof init === "object" && init !== null) {
+                if (typeof init[Symbol.iterator] === "function") {
+                    for (const pair of init) {
+                        const entry = [...pair];
+                        if (entry.length !== 2) {
+                            throw new TypeError("Each header pair must be an iterable [name, value] tuple.");
+                        }
+                        this.append(entry[0], entry[1]);
+                    }
+                } else {
+                    for (const key of Object.keys(init)) {
+                        this.append(key, init[key]);
+                    }
+                }
+            }
+        }
+
+        append(name, value) {
+            this.#list.push([normalizeName(name), normalizeValue(value)]);
+        }
+
+        delete(name) {
+            name = normalizeName(name);
+            this.#list = this.#list.filter(([n]) => n !== name);
+        }
+
+        get(name) {
+            name = normalizeName(name);
+            const values = this.#list.filter(([n]) => n === name).map(([, v]) => v);
+            return values.length === 0 ? null : values.join(", ");
+        }
+
+        getSetCookie() {
+            return this.#list.filter(([n]) => n === "set-cookie").map(([, v]) => v);
+        }
+
+        has(name) {
+            name = normalizeName(name);
+            return this.#list.some(([n]) => n === name);
+        }
+
+        set(name, value) {
+            name = normalizeName(name);
+            this.delete(name);
+            this.#list.push([name, normalizeValue(value)]);
+        }
+
+        forEach(callback, thisArg) {
+            for (const [name, value] of this) {
+                callback.call(thisArg, value, name, this);
+            }
+        }
+
+        *entries() {
+            const names = [...new Set(this.#list.map(([n]) => n))].sort();
+            for (const name of names) {
+                if (name === "set-cookie") {
+                    for (const value of this.getSetCookie()) {
+                        yield [name, value];
+                    }
+                } else {
+                    yield [name, this.get(name)];
+                }
+            }
+        }
+
+        *keys() {
+            for (const [name] of this.entries()) {
+                yield name;
+            }
+        }
+
+        *values() {
+            for (const [, value] of this.entries()) {
+                yield value;
+            }
+        }
+
+        [Symbol.iterator]() {
+            return this.entries();
+        }
+    }
+
+    function extractBody(body, headers) {
+        if (body === undefined || body === null) {
+            return null;
+        }
+        if (body instanceof ArrayBuffer) {
+            return new Uint8Array(body.slice(0));
+        }
+        if (ArrayBuffer.isView(body)) {
+            return new Uint8Array(body.buffer.slice(body.byteOffset, body.byteOffset + body.byteLength));
+        }
+        if (body instanceof URLSearchParams) {
+            if (!headers.has("content-type")) {
+                headers.set("content-type", "application/x-www-form-urlencoded;charset=UTF-8");
+            }
+            return new TextEncoder().encode(body.toString());
+        }
+        if (!headers.has("content-type")) {
+            headers.set("content-type", "text/plain;charset=UTF-8");
+        }
+        return new TextEncoder().encode(String(body));
+    }
+
+    class Body {
+        #bytes;
+        #handle;
+        #stream = null;
+        #used = false;
+
+        constructor(bytes, handle) {
+            this.#bytes = bytes;
+            this.#handle = handle;
+        }
+
+        get body() {
+            if (this.#bytes === null && this.#handle === null) {
+                return null;
+            }
+            if (this.#stream === null) {
+                const body = this;
+                let reading = false;
+                // a chunk is read whenever the script asks for one, blocking until the handler has it
+                this.#stream = new ReadableStream({
+                    pull(controller) {
+                        if (!reading) {
+                            if (body.#used) {
+                                throw new TypeError("Body has already been consumed.");
+                            }
+                            body.#used = true;
+                            reading = true;
+                            if (body.#handle === null) {
+                                controller.enqueue(body.#bytes);
+                                controller.close();
+                                return;
+                            }
+                        }
+                        const chunk = native.readChunk(body.#handle);
+                        if (chunk === null) {
+                            controller.close();
+                        } else {
+                            controller.enqueue(chunk);
+                        }
+                    },
+                }, { highWaterMark: 0 });
+            }
+            return this.#stream;
+        }
+
+        get bodyUsed() {
+            return this.#used;
+        }
+
+        async bytes() {
+            if (this.#used || this.#stream?.locked) {
+                throw new TypeError("Body has already been consumed.");
+            }
+            this.#used = true;
+            if (this.#handle !== null) {
+                return native.readAll(this.#handle);
+            }
+            return this.#bytes === null ? new Uint8Array(0) : this.#bytes;
+        }
+
+        async arrayBuffer() {
+            const bytes = await this.bytes();
+            return bytes.buffer.slice(bytes.byteOffset, bytes.byteOffset + bytes.byteLength);
+        }
+
+        async text() {
+            return new TextDecoder().decode(await this.bytes());
+        }
+
+        async json() {
+            return JSON.parse(await this.text());
+        }
+    }
+
+    class Request extends Body {
+        #method;
+        #url;
+        #headers;
+
+        constructor(input, init = {}) {
+            const source = input instanceof Request ? input : null;
+            const headers = new Headers(init.headers ?? source?.headers);
+            const method = String(init.method ?? source?.method ?? "GET").toUpperCase();
+            const body = extractBody(init.body, headers);
+            if (body !== null && (method === "GET" || method === "HEAD")) {
+                throw new TypeError("Request with GET/HEAD method cannot have body.");
+            }
+            super(body, null);
+            this.#url = new URL(source === null ? String(input) : source.url).href;
+            this.#method = method;
+            this.#headers = headers;
+        }
+
+        get method() {
+            return this.#method;
+        }
+
+        get url() {
+            return this.#url;
+        }
+
+        get headers() {
+            return this.#headers;
+        }
+    }
+
+    class Response extends Body {
+        #status;
+        #statusText;
+        #headers;
+        #url;
+
+        constructor(body = null, init = {}, url = "", handle = null) {
+            const headers = new Headers(init.headers);
+            super(handle === null ? extractBody(body, headers) : null, handle);
+            this.#status = init.status ?? 200;
+            if (this.#status < 200 || this.#status > 599) {
+                throw new RangeError(`Invalid status code: ${this.#status}`);
+            }
+            this.#statusText = String(init.statusText ?? "");
+            this.#headers = headers;
+            this.#url = url;
+        }
+
+        static json(data, init = {}) {
+            const headers = new Headers(init.headers);
+            if (!headers.has("content-type")) {
+                headers.set("content-type", "application/json");
+            }
+            return new Response(JSON.stringify(data), { ...init, headers });
+        }
+
+        get type() {
+            return "basic";
+        }
+
+        get url() {
+            return this.#url;
+        }
+
+        get redirected() {
+            return false;
+        }
+
+        get status() {
+            return this.#status;
+        }
+
+        get ok() {
+            return this.#status >= 200 && this.#status <= 299;
+        }
+
+        get statusText() {
+            return this.#statusText;
+        }
+
+        get headers() {
+            return this.#headers;
+        }
+    }
+
+    async function fetch(input, init = {}) {
+        const request = new Request(input, init);
+        const body = request.method === "GET" || request.method === "HEAD" ? null : await request.bytes();
+        let result;
+        try {
+            result = native.send(request.method, request.url, [...request.headers], body);
+        } catch (error) {
+            throw new TypeError("fetch failed", { cause: error });
+        }
+        const { status, statusText, headers, handle } = result;
+        return new Response(null, { status, statusText, headers }, request.url, handle);
+    }
+
+    return { Headers, Request, Response, fetch };
+})"#;
+
+pub struct Request {
+    pub method: String,
+    pub url: String,
+    pub headers: Vec<(String, String)>,
+    pub body: Option<Vec<u8>>,
+}
+
+impl Request {
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(key, _)| key.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+}
+
+pub struct Response {
+    pub status: u16,
+    pub status_text: String,
+    pub headers: Vec<(String, String)>,
+    pub body: Box<dyn Read>,
+}
+
+impl Response {
+    pub fn new(status: u16, body: impl Read + 'static) -> Self {
+        Self {
+            status,
+            status_text: String::new(),
+            headers: vec![],
+            body: Box::new(body),
+        }
+    }
+
+    pub fn empty(status: u16) -> Self {
+        Self::new(status, std::io::empty())
+    }
+
+    pub fn with_status_text(mut self, status_text: impl Into<String>) -> Self {
+        self.status_text = status_text.into();
+        self
+    }
+
+    pub fn with_header(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.headers.push((name.into(), value.into()));
+        self
+    }
+}
+
+// handlers run on the JS thread: `fetch` and every read of a response body block the event loop
+// until the handler or its `Read` returns, so slow sources are best read ahead of time
+pub trait FetchHandler {
+    fn fetch(&self, request: Request) -> Result<Response, String>;
+}
+
+impl<F: Fn(Request) -> Result<Response, String>> FetchHandler for F {
+    fn fetch(&self, request: Request) -> Result<Response, String> {
+        (self)(request)
+    }
+}
+
+pub struct FileHandler {
+    root: PathBuf,
+}
+
+impl FileHandler {
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self { root: root.into() }
+    }
+}
+
+impl FetchHandler for FileHandler {
+    fn fetch(&self, request: Request) -> Result<Response, String> {
+        let url = Url::parse(&request.url).map_err(|err| err.to_string())?;
+        if url.scheme() != "file" {
+            return Err(format!("Unsupported URL scheme: {}", url.scheme()));
+        }
+        let path = url
+            .to_file_path()
+            .map_err(|_| format!("Invalid file URL: {}", request.url))?;
+        let root = self.root.canonicalize().map_err(|err| err.to_string())?;
+        let denied = || Err(format!("Access denied: {}", request.url));
+        // paths outside the root are refused before touching the filesystem, so scripts can't
+        // probe which files exist
+        let path = normalize(&path);
+        let lexical_root = std::env::current_dir()
+            .map(|dir| normalize(&dir.join(&self.root)))
+            .map_err(|err| err.to_string())?;
+        if !path.starts_with(&root) && !path.starts_with(&lexical_root) {
+            return denied();
+        }
+        let Ok(path) = path.canonicalize() else {
+            return Ok(Response::empty(404).with_status_text("Not Found"));
+        };
+        // a symlink inside the root can still lead out of it
+        if !path.starts_with(&root) {
+            return denied();
+        }
+        if request.method != "GET" && request.method != "HEAD" {
+            return Ok(Response::empty(405).with_status_text("Method Not Allowed"));
+        }
+        let Ok(file) = File::open(&path) else {
+            return Ok(Response::empty(404).with_status_text("Not Found"));
+        };
+        let mut response = if request.method == "HEAD" {
+            Response::empty(200)
+        } else {
+            Response::new(200, file)
+        };
+        response = response
+            .with_status_text("OK")
+            .with_header("content-type", content_type(&path));
+        if let Ok(metadata) = path.metadata() {
+            response = response.with_header("content-length", metadata.len().to_string());
+        }
+        Ok(response)
+    }
+}
+
+// resolves `.` and `..` without following symlinks
+fn normalize(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                normalized.pop();
+            }
+            component => normalized.push(component),
+        }
+    }
+    normalized
+}
+
+fn content_type(path: &Path) -> &'static str {
+    match path.extension().and_then(|extension| extension.to_str()) {
+        Some("js" | "mjs") => "text/javascript",
+        Some("json") => "application/json",
+        Some("html" | "htm") => "text/html",
+        Some("css") => "text/css",
+        Some("txt") => "text/plain",
+        Some("svg") => "image/svg+xml",
+        Some("png") => "image/png",
+        Some("jpg" | "jpeg") => "image/jpeg",
+        Some("wasm") => "application/wasm",
+        _ => "application/octet-stream",
+    }
+}
+
+pub fn set_handler(handler: impl FetchHandler + 'static) {
+    unsafe {
+        *HANDLER.get() = Some(Box::new(handler));
+    }
+}
+
+pub fn clear_handler() {
+    unsafe {
+        *HANDLER.get() = None;
+    }
+}
+
+type Body = RefCell<Box<dyn Read>>;
+
+pub(crate) fn install() {
+    let native = Object::new();
+    native.set(
+        "send",
+        Function::new(|args| {
+            let handler = unsafe { HANDLER.get().as_ref() }.or_throw("No fetch handler is set.")?;
+            let headers = args.get(2).into_array().unwrap_or_else(Array::new);
+            let request = Request {
+                method: args.get(0).into_string().unwrap_or_default(),
+                url: args.get(1).into_string().unwrap_or_default(),
+                headers: (0..headers.length())
+                    .filter_map(|i| {
+                        let pair = headers.get(i).into_array()?;
+                        Some((pair.get(0).into_string()?, pair.get(1).into_string()?))
+                    })
+                    .collect(),
+                body: to_bytes(args.get(3)),
+            };
+            let response = handler.fetch(request).map_err(Exception::msg)?;
+            let result = Object::new();
+            result.set("status", response.status as f64);
+            result.set("statusText", response.status_text);
+            result.set(
+                "headers",
+                response
+                    .headers
+                    .into_iter()
+                    .map(|(name, value)| Value::from(vec![name.into(), value.into()]))
+                    .collect::<Vec<_>>(),
+            );
+            let body: Body = RefCell::new(response.body);
+            result.set("handle", native::wrap(body));
+            Ok(result)
+        }),
+    );
+    native.set(
+        "readChunk",
+        Function::new(|args| {
+            let handle = args.get(0).into_object().or_throw("Invalid body handle.")?;
+            let body = native::get::<Body>(&handle).or_throw("Invalid body handle.")?;
+            let mut chunk = vec![0; 65536];
+            let length = body
+                .borrow_mut()
+                .read(&mut chunk)
+                .map_err(|err| Exception::msg(err.to_string()))?;
+            if length == 0 {
+                Ok(Value::Null)
+            } else {
+                chunk.truncate(length);
+                Ok(from_bytes(chunk))
+            }
+        }),
+    );
+    native.set(
+        "readAll",
+        Function::new(|args| {
+            let handle = args.get(0).into_object().or_throw("Invalid body handle.")?;
+            let body = native::get::<Body>(&handle).or_throw("Invalid body handle.")?;
+            let mut bytes = vec![];
+            body.borrow_mut()
+                .read_to_end(&mut bytes)
+                .map_err(|err| Exception::msg(err.to_string()))?;
+            Ok(from_bytes(bytes))
+        }),
+    );
+
+    let factory = eval(GLUE).unwrap().into_function().unwrap();
+    let globals = factory.call([native.into()]).unwrap().into_object().unwrap();
+    for name in globals.keys() {
+        global_set(&name, globals.get(&name));
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::io::Cursor;
+
+    use crate::{eval, event_loop, fetch, Value};
+
+    #[test]
+    fn fetch_with_handler() {
+        fetch::set_handler(|request: fetch::Request| -> Result<fetch::Response, String> {
+            assert_eq!(request.method, "POST");
+            assert_eq!(request.header("X-Test"), Some("yes"));
+            let body = String::from_utf8(request.body.unwrap()).unwrap();
+            let json = format!("{{\"url\":\"{}\",\"body\":\"{}\"}}", request.url, body);
+            Ok(fetch::Response::new(201, Cursor::new(json))
+                .with_header("content-type", "application/json"))
+        });
+        eval(
+            "globalThis.result = null;
+            fetch('https://example.com/api', { method: 'post', headers: { 'x-test': 'yes' }, body: 'hi' })
+                .then(async (response) => {
+                    const json = await response.json();
+                    result = [response.status, response.headers.get('content-type'), json.url, json.body].join();
+                });",
+        )
+        .unwrap();
+        event_loop::run_until_idle().unwrap();
+        assert_eq!(
+            eval("result").unwrap(),
+            Value::from("201,application/json,https://example.com/api,hi")
+        );
+        fetch::clear_handler();
+    }
+
+    #[test]
+    fn fetch_body_stream() {
+        fetch::set_handler(|_: fetch::Request| -> Result<fetch::Response, String> {
+            Ok(fetch::Response::new(200, Cursor::new(vec![b'a'; 100000])))
+        });
+        eval(
+            "globalThis.result = null;
+            fetch('https://example.com/').then(async (response) => {
+                const stream = response.body;
+                const reader = stream.getReader();
+                let chunks = 0, length = 0;
+                for (;;) {
+                    const { done, value } = await reader.read();
+                    if (done) break;
+                    chunks++;
+                    length += value.length;
+                }
+                const rejected = await response.text().then(() => false, (error) => error instanceof TypeError);
+                result = [stream instanceof ReadableStream, response.body === stream, chunks, length, response.bodyUsed, rejected].join();
+            });",
+        )
+        .unwrap();
+        event_loop::run_until_idle().unwrap();
+        assert_eq!(
+            eval("result").unwrap(),
+            Value::from("true,true,2,100000,true,true")
+        );
+        fetch::clear_handler();
+    }
+
+    #[test]
+    fn fetch_error() {
+        fetch::set_handler(|_: fetch::Request| -> Result<fetch::Response, String> {
+            Err("offline".to_owned())
+        });
+        eval(
+            "globalThis.result = null;
+            fetch('https://example.com/').catch((error) => result = `${error.name}: ${error.cause}`);",
+        )
+        .unwrap();
+        event_loop::run_until_idle().unwrap();
+        assert_eq!(eval("result").unwrap(), Value::from("TypeError: offline"));
+        fetch::clear_handler();
+    }
+
+    #[test]
+    fn fetch_file() {
+        let root = std::env::temp_dir().join("unijs3_fetch_file");
+        std::fs::create_dir_all(&root).unwrap();
+        std::fs::write(root.join("data.txt"), "hello from disk").unwrap();
+        let outside = std::env::temp_dir().join("unijs3_fetch_outside.txt");
+        std::fs::write(&outside, "secret").unwrap();
+        fetch::set_handler(fetch::FileHandler::new(&root));
+        let url = url::Url::from_file_path(root.join("data.txt").canonicalize().unwrap()).unwrap();
+        let outside_url = url::Url::from_file_path(outside.canonicalize().unwrap()).unwrap();
+        let missing = outside.with_file_name("unijs3_fetch_missing.txt");
+        let missing_url = url::Url::from_file_path(missing).unwrap();
+        eval(format!(
+            "globalThis.result = null;
+            globalThis.denied = null;
+            globalThis.missing = null;
+            fetch('{}').then((response) => response.text()).then((text) => result = text);
+            fetch('{}').catch((error) => denied = error.cause);
+            fetch('{}').catch((error) => missing = error.cause);",
+            url, outside_url, missing_url
+        ))
+        .unwrap();
+        event_loop::run_until_idle().unwrap();
+        assert_eq!(eval("result").unwrap(), Value::from("hello from disk"));
+        assert!(eval("denied")
+            .unwrap()
+            .into_string()
+            .unwrap()
+            .starts_with("Access denied"));
+        assert!(eval("missing")
+            .unwrap()
+            .into_string()
+            .unwrap()
+            .starts_with("Access denied"));
+        fetch::clear_handler();
+    }
+}
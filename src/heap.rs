@@ -0,0 +1,425 @@
+#[cfg(engine = "v8")]
+use std::ffi::c_void;
+
+use crate::thread_state::ThreadState;
+
+thread_state! {
+    #[cfg(not(engine = "v8"))]
+    static EXTERNAL_MEMORY: i64 = 0;
+    static GC_CALLBACKS: Vec<GcCallback> = Vec::new();
+    static NEXT_CALLBACK_ID: u32 = 1;
+    #[cfg(engine = "v8")]
+    static TRAMPOLINES_INSTALLED: bool = false;
+    // set while callbacks run, removals are then recorded and applied once they're done
+    #[cfg(engine = "v8")]
+    static DISPATCHING: bool = false;
+    #[cfg(engine = "v8")]
+    static REMOVED: Vec<u32> = Vec::new();
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct HeapStatistics {
+    pub total_heap_size: usize,
+    pub total_heap_size_executable: usize,
+    pub total_physical_size: usize,
+    pub total_available_size: usize,
+    pub used_heap_size: usize,
+    pub heap_size_limit: usize,
+    pub malloced_memory: usize,
+    pub external_memory: usize,
+    pub spaces: Vec<HeapSpaceStatistics>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct HeapSpaceStatistics {
+    pub name: String,
+    pub size: usize,
+    pub used_size: usize,
+    pub available_size: usize,
+    pub physical_size: usize,
+}
+
+// returns None when the engine does not expose heap statistics (e.g. non-Chromium browsers)
+pub fn statistics() -> Option<HeapStatistics> {
+    #[cfg(engine = "v8")]
+    {
+        let scope = crate::v8::scope();
+        let mut statistics = v8::HeapStatistics::default();
+        scope.get_heap_statistics(&mut statistics);
+        let spaces = (0..scope.number_of_heap_spaces())
+            .filter_map(|index| {
+                let mut space = v8::HeapSpaceStatistics::default();
+                if !scope.get_heap_space_statistics(&mut space, index) {
+                    return None;
+                }
+                Some(HeapSpaceStatistics {
+                    name: space.space_name().to_string_lossy().into_owned(),
+                    size: space.space_size(),
+                    used_size: space.space_used_size(),
+                    available_size: space.space_available_size(),
+                    physical_size: space.physical_space_size(),
+                })
+            })
+            .collect();
+        Some(HeapStatistics {
+            total_heap_size: statistics.total_heap_size(),
+            total_heap_size_executable: statistics.total_heap_size_executable(),
+            total_physical_size: statistics.total_physical_size(),
+            total_available_size: statistics.total_available_size(),
+            used_heap_size: statistics.used_heap_size(),
+            heap_size_limit: statistics.heap_size_limit(),
+            malloced_memory: statistics.malloced_memory(),
+            external_memory: statistics.external_memory(),
+            spaces,
+        })
+    }
+    #[cfg(engine = "browser")]
+    {
+        let memory = crate::eval("globalThis.performance && performance.memory")
+            .ok()?
+            .into_object()?;
+        let size = |key: &str| memory.get(key).into_number().unwrap_or(0.) as usize;
+        let total_heap_size = size("totalJSHeapSize");
+        let used_heap_size = size("usedJSHeapSize");
+        let heap_size_limit = size("jsHeapSizeLimit");
+        Some(HeapStatistics {
+            total_heap_size,
+            total_available_size: heap_size_limit.saturating_sub(used_heap_size),
+            used_heap_size,
+            heap_size_limit,
+            external_memory: unsafe { *EXTERNAL_MEMORY.get() }.max(0) as usize,
+            ..Default::default()
+        })
+    }
+    #[cfg(engine = "quickjs")]
+    {
+        let usage = crate::quickjs::runtime().memory_usage();
+        let total_heap_size = usage.malloc_size.max(0) as usize;
+        let used_heap_size = usage.memory_used_size.max(0) as usize;
+        // a negative limit means there is none
+        let heap_size_limit = usage.malloc_limit.max(0) as usize;
+        Some(HeapStatistics {
+            total_heap_size,
+            total_available_size: heap_size_limit.saturating_sub(total_heap_size),
+            used_heap_size,
+            heap_size_limit,
+            malloced_memory: total_heap_size,
+            external_memory: unsafe { *EXTERNAL_MEMORY.get() }.max(0) as usize,
+            ..Default::default()
+        })
+    }
+    // boa doesn't track its heap size
+    #[cfg(engine = "boa")]
+    {
+        None
+    }
+}
+
+// tells the engine how much memory outside its heap is kept alive by script objects, returns the new total
+pub fn adjust_external_memory(delta: i64) -> i64 {
+    #[cfg(engine = "v8")]
+    {
+        let scope = crate::v8::scope();
+        scope.adjust_amount_of_external_allocated_memory(delta)
+    }
+    #[cfg(not(engine = "v8"))]
+    unsafe {
+        *EXTERNAL_MEMORY.get() += delta;
+        *EXTERNAL_MEMORY.get()
+    }
+}
+
+// forces a full collection and runs the finalizers it scheduled, intended for tests only
+pub fn collect_garbage() {
+    #[cfg(engine = "v8")]
+    {
+        let scope = crate::v8::scope();
+        scope.clear_kept_objects();
+        scope.low_memory_notification();
+        // FinalizationRegistry cleanup is posted as a platform task
+        crate::v8::pump_platform();
+        scope.perform_microtask_checkpoint();
+    }
+    #[cfg(engine = "quickjs")]
+    {
+        crate::quickjs::runtime().run_gc();
+        crate::quickjs::run_pending_jobs();
+    }
+    #[cfg(engine = "boa")]
+    {
+        boa_gc::force_collect();
+        crate::boa::run_jobs();
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GcKind {
+    Scavenge,
+    MinorMarkSweep,
+    MarkSweepCompact,
+    IncrementalMarking,
+    ProcessWeakCallbacks,
+    Other,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum GcPhase {
+    Prologue,
+    Epilogue,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct GcCallbackId(u32);
+
+#[cfg_attr(not(engine = "v8"), allow(dead_code))]
+struct GcCallback {
+    id: u32,
+    phase: GcPhase,
+    callback: Box<dyn FnMut(GcKind)>,
+}
+
+#[cfg(engine = "v8")]
+impl From<v8::GCType> for GcKind {
+    fn from(kind: v8::GCType) -> Self {
+        if kind == v8::GCType::kGCTypeScavenge {
+            Self::Scavenge
+        } else if kind == v8::GCType::kGCTypeMinorMarkSweep {
+            Self::MinorMarkSweep
+        } else if kind == v8::GCType::kGCTypeMarkSweepCompact {
+            Self::MarkSweepCompact
+        } else if kind == v8::GCType::kGCTypeIncrementalMarking {
+            Self::IncrementalMarking
+        } else if kind == v8::GCType::kGCTypeProcessWeakCallbacks {
+            Self::ProcessWeakCallbacks
+        } else {
+            Self::Other
+        }
+    }
+}
+
+#[cfg(engine = "v8")]
+fn dispatch(phase: GcPhase, kind: GcKind) {
+    // callbacks run while the heap is being collected and must not call into script. They may
+    // add or remove callbacks, so the list is taken out while they run and merged back after
+    let mut callbacks = unsafe { std::mem::take(GC_CALLBACKS.get()) };
+    unsafe { *DISPATCHING.get() = true };
+    for callback in callbacks.iter_mut() {
+        if callback.phase == phase && !unsafe { REMOVED.get().contains(&callback.id) } {
+            (callback.callback)(kind);
+        }
+    }
+    unsafe {
+        *DISPATCHING.get() = false;
+        let removed = std::mem::take(REMOVED.get());
+        callbacks.retain(|callback| !removed.contains(&callback.id));
+        callbacks.append(GC_CALLBACKS.get());
+        *GC_CALLBACKS.get() = callbacks;
+    }
+}
+
+#[cfg(engine = "v8")]
+extern "C" fn prologue(
+    _isolate: *mut v8::Isolate,
+    kind: v8::GCType,
+    _flags: v8::GCCallbackFlags,
+    _data: *mut c_void,
+) {
+    dispatch(GcPhase::Prologue, kind.into());
+}
+
+#[cfg(engine = "v8")]
+extern "C" fn epilogue(
+    _isolate: *mut v8::Isolate,
+    kind: v8::GCType,
+    _flags: v8::GCCallbackFlags,
+    _data: *mut c_void,
+) {
+    dispatch(GcPhase::Epilogue, kind.into());
+}
+
+fn add_gc_callback(phase: GcPhase, callback: Box<dyn FnMut(GcKind)>) -> GcCallbackId {
+    #[cfg(engine = "v8")]
+    unsafe {
+        if !*TRAMPOLINES_INSTALLED.get() {
+            *TRAMPOLINES_INSTALLED.get() = true;
+            let scope = crate::v8::scope();
+            scope.add_gc_prologue_callback(prologue, std::ptr::null_mut(), v8::GCType::kGCTypeAll);
+            scope.add_gc_epilogue_callback(epilogue, std::ptr::null_mut(), v8::GCType::kGCTypeAll);
+        }
+    }
+    unsafe {
+        let id = *NEXT_CALLBACK_ID.get();
+        *NEXT_CALLBACK_ID.get() += 1;
+        GC_CALLBACKS.get().push(GcCallback {
+            id,
+            phase,
+            callback,
+        });
+        GcCallbackId(id)
+    }
+}
+
+// only v8 exposes its collector, with other engines the callbacks are never invoked
+pub fn on_gc_prologue(callback: impl FnMut(GcKind) + 'static) -> GcCallbackId {
+    add_gc_callback(GcPhase::Prologue, Box::new(callback))
+}
+
+pub fn on_gc_epilogue(callback: impl FnMut(GcKind) + 'static) -> GcCallbackId {
+    add_gc_callback(GcPhase::Epilogue, Box::new(callback))
+}
+
+pub fn remove_gc_callback(id: GcCallbackId) {
+    unsafe {
+        #[cfg(engine = "v8")]
+        if *DISPATCHING.get() {
+            REMOVED.get().push(id.0);
+        }
+        GC_CALLBACKS.get().retain(|callback| callback.id != id.0);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    #[cfg(engine = "browser")]
+    use wasm_bindgen_test::wasm_bindgen_test as test;
+
+    use crate::heap;
+
+    #[test]
+    fn statistics() {
+        #[cfg(engine = "v8")]
+        {
+            let statistics = heap::statistics().unwrap();
+            assert!(statistics.used_heap_size > 0);
+            assert!(statistics.used_heap_size <= statistics.total_heap_size);
+            assert!(statistics.total_heap_size <= statistics.heap_size_limit);
+            assert!(statistics
+                .spaces
+                .iter()
+                .any(|space| space.name == "old_space"));
+        }
+        #[cfg(engine = "browser")]
+        {
+            if let Some(statistics) = heap::statistics() {
+                assert!(statistics.used_heap_size <= statistics.total_heap_size);
+            }
+        }
+        #[cfg(engine = "quickjs")]
+        {
+            let statistics = heap::statistics().unwrap();
+            assert!(statistics.used_heap_size > 0);
+        }
+        #[cfg(engine = "boa")]
+        assert!(heap::statistics().is_none());
+    }
+
+    #[test]
+    fn external_memory() {
+        let before = heap::adjust_external_memory(0);
+        assert_eq!(heap::adjust_external_memory(1024), before + 1024);
+        assert_eq!(heap::adjust_external_memory(-1024), before);
+    }
+
+    #[test]
+    fn external_size_stays_with_the_value() {
+        use crate::{eval, global_set, native};
+
+        let before = heap::adjust_external_memory(0);
+        let wrapped = native::wrap_with_size(vec![0u8; 1024], 1024);
+        global_set("sized", wrapped.clone());
+        // scripts can't change what is given back once the value is taken or dropped
+        eval("sized._size = 1e12; Object.defineProperty(sized, 'size', { value: -1 })").unwrap();
+        assert_eq!(heap::adjust_external_memory(0), before + 1024);
+        assert_eq!(native::take::<Vec<u8>>(wrapped).unwrap().len(), 1024);
+        assert_eq!(heap::adjust_external_memory(0), before);
+    }
+
+    #[test]
+    fn finalizer_after_collect_garbage() {
+        #[cfg(engine = "v8")]
+        {
+            use std::{cell::Cell, rc::Rc};
+
+            use crate::{eval, global_set, native, Function};
+
+            struct Buffer(#[allow(dead_code)] Vec<u8>, Rc<Cell<bool>>);
+            impl Drop for Buffer {
+                fn drop(&mut self) {
+                    self.1.set(true);
+                }
+            }
+            let dropped = Rc::new(Cell::new(false));
+            let flag = dropped.clone();
+            // wrapping inside a callback keeps the object out of the long-lived top level handle scope
+            global_set(
+                "makeBuffer",
+                Function::new(move |_| {
+                    let buffer = Buffer(vec![0; 1 << 20], flag.clone());
+                    Ok(native::wrap_with_size(buffer, 1 << 20))
+                }),
+            );
+            let before = heap::adjust_external_memory(0);
+            eval("makeBuffer(); undefined").unwrap();
+            assert_eq!(heap::adjust_external_memory(0), before + (1 << 20));
+            heap::collect_garbage();
+            assert!(dropped.get());
+            assert_eq!(heap::adjust_external_memory(0), before);
+        }
+    }
+
+    #[test]
+    fn gc_callbacks() {
+        #[cfg(engine = "v8")]
+        {
+            use std::{cell::Cell, rc::Rc};
+
+            let prologues = Rc::new(Cell::new(0));
+            let epilogues = Rc::new(Cell::new(0));
+            let counter = prologues.clone();
+            let prologue = heap::on_gc_prologue(move |_| counter.set(counter.get() + 1));
+            let counter = epilogues.clone();
+            let epilogue = heap::on_gc_epilogue(move |kind| {
+                if kind == heap::GcKind::MarkSweepCompact {
+                    counter.set(counter.get() + 1);
+                }
+            });
+            heap::collect_garbage();
+            assert!(prologues.get() > 0);
+            assert!(epilogues.get() > 0);
+            heap::remove_gc_callback(prologue);
+            heap::remove_gc_callback(epilogue);
+            let count = prologues.get();
+            heap::collect_garbage();
+            assert_eq!(prologues.get(), count);
+        }
+    }
+
+    #[test]
+    fn gc_callbacks_changed_during_gc() {
+        #[cfg(engine = "v8")]
+        {
+            use std::{cell::Cell, rc::Rc};
+
+            let own_id = Rc::new(Cell::new(None));
+            let added = Rc::new(Cell::new(None));
+            let calls = Rc::new(Cell::new(0));
+            let (id, slot, counter) = (own_id.clone(), added.clone(), calls.clone());
+            let prologue = heap::on_gc_prologue(move |_| {
+                if let Some(id) = id.take() {
+                    heap::remove_gc_callback(id);
+                    let counter = counter.clone();
+                    slot.set(Some(heap::on_gc_epilogue(move |_| {
+                        counter.set(counter.get() + 1)
+                    })));
+                }
+            });
+            own_id.set(Some(prologue));
+            heap::collect_garbage();
+            assert!(calls.get() > 0);
+            heap::remove_gc_callback(added.take().unwrap());
+            let count = calls.get();
+            heap::collect_garbage();
+            assert_eq!(calls.get(), count);
+        }
+    }
+}
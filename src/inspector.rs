@@ -0,0 +1,413 @@
+use std::{
+    collections::HashMap,
+    io::{ErrorKind, Read, Write},
+    net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs},
+    sync::{
+        atomic::{AtomicU32, Ordering},
+        mpsc::{channel, Receiver, Sender},
+    },
+    time::Duration,
+};
+
+use v8::inspector::{
+    ChannelBase, ChannelImpl, StringBuffer, StringView, V8Inspector, V8InspectorClientBase,
+    V8InspectorClientImpl, V8InspectorClientTrustLevel, V8InspectorSession,
+};
+
+use crate::thread_state::ThreadState;
+
+const CONTEXT_GROUP_ID: i32 = 1;
+
+thread_state! {
+    static INSPECTOR: Option<Inspector> = None;
+    // events for this thread's engine, sessions keep a sender so they stay with the engine they
+    // were connected to
+    static QUEUE: Option<(Sender<Event>, Receiver<Event>)> = None;
+}
+static NEXT_SESSION_ID: AtomicU32 = AtomicU32::new(1);
+
+enum Event {
+    Connect { id: u32, outgoing: Sender<String> },
+    Message { id: u32, message: String },
+    Disconnect { id: u32 },
+}
+
+struct Client {
+    base: V8InspectorClientBase,
+}
+
+impl V8InspectorClientImpl for Client {
+    fn base(&self) -> &V8InspectorClientBase {
+        &self.base
+    }
+
+    fn base_mut(&mut self) -> &mut V8InspectorClientBase {
+        &mut self.base
+    }
+
+    unsafe fn base_ptr(this: *const Self) -> *const V8InspectorClientBase {
+        std::ptr::addr_of!((*this).base)
+    }
+
+    fn run_message_loop_on_pause(&mut self, _context_group_id: i32) {
+        inspector().paused = true;
+        while inspector().paused {
+            pump(true);
+        }
+    }
+
+    fn quit_message_loop_on_pause(&mut self) {
+        inspector().paused = false;
+    }
+
+    fn run_if_waiting_for_debugger(&mut self, _context_group_id: i32) {
+        inspector().waiting_for_debugger = false;
+    }
+}
+
+struct Channel {
+    base: ChannelBase,
+    outgoing: Sender<String>,
+}
+
+impl ChannelImpl for Channel {
+    fn base(&self) -> &ChannelBase {
+        &self.base
+    }
+
+    fn base_mut(&mut self) -> &mut ChannelBase {
+        &mut self.base
+    }
+
+    unsafe fn base_ptr(this: *const Self) -> *const ChannelBase {
+        std::ptr::addr_of!((*this).base)
+    }
+
+    fn send_response(&mut self, _call_id: i32, message: v8::UniquePtr<StringBuffer>) {
+        let _ = self.outgoing.send(message.unwrap().string().to_string());
+    }
+
+    fn send_notification(&mut self, message: v8::UniquePtr<StringBuffer>) {
+        let _ = self.outgoing.send(message.unwrap().string().to_string());
+    }
+
+    fn flush_protocol_notifications(&mut self) {}
+}
+
+struct SessionState {
+    session: v8::UniqueRef<V8InspectorSession>,
+    _channel: Box<Channel>,
+}
+
+// fields are declared in drop order: sessions before the inspector, the inspector before its client
+struct Inspector {
+    sessions: HashMap<u32, SessionState>,
+    inspector: v8::UniqueRef<V8Inspector>,
+    _client: Box<Client>,
+    paused: bool,
+    waiting_for_debugger: bool,
+}
+
+fn inspector() -> &'static mut Inspector {
+    unsafe {
+        INSPECTOR.get().get_or_insert_with(|| {
+            let scope = crate::v8::scope();
+            let mut client = Box::new(Client {
+                base: V8InspectorClientBase::new::<Client>(),
+            });
+            let inspector = V8Inspector::create(scope, &mut *client);
+            Inspector {
+                sessions: HashMap::new(),
+                inspector,
+                _client: client,
+                paused: false,
+                waiting_for_debugger: false,
+            }
+        })
+    }
+}
+
+fn queue() -> &'static (Sender<Event>, Receiver<Event>) {
+    unsafe { QUEUE.get().get_or_insert_with(channel) }
+}
+
+pub(crate) fn context_created() {
+    let context = *crate::v8::context();
+    inspector().inspector.context_created(
+        context,
+        CONTEXT_GROUP_ID,
+        StringView::from(&b"unijs3"[..]),
+        StringView::from(&br#"{"isDefault": true}"#[..]),
+    );
+}
+
+pub(crate) fn context_destroyed() {
+    let context = *crate::v8::context();
+    inspector().inspector.context_destroyed(context);
+}
+
+// handles one queued event, blocking until there is one if requested
+fn pump(block: bool) -> bool {
+    let event = {
+        let receiver = &queue().1;
+        if block {
+            receiver.recv().ok()
+        } else {
+            receiver.try_recv().ok()
+        }
+    };
+    let Some(event) = event else {
+        return false;
+    };
+    let inspector = inspector();
+    match event {
+        Event::Connect { id, outgoing } => {
+            let mut channel = Box::new(Channel {
+                base: ChannelBase::new::<Channel>(),
+                outgoing,
+            });
+            let session = inspector.inspector.connect(
+                CONTEXT_GROUP_ID,
+                &mut *channel,
+                StringView::empty(),
+                V8InspectorClientTrustLevel::FullyTrusted,
+            );
+            inspector.sessions.insert(
+                id,
+                SessionState {
+                    session,
+                    _channel: channel,
+                },
+            );
+        }
+        Event::Message { id, message } => {
+            if let Some(state) = inspector.sessions.get_mut(&id) {
+                // dispatching can re-enter the pause loop, which pumps more events
+                let session: *mut V8InspectorSession = &mut *state.session;
+                unsafe {
+                    (*session).dispatch_protocol_message(StringView::from(message.as_bytes()));
+                }
+            }
+        }
+        Event::Disconnect { id } => {
+            inspector.sessions.remove(&id);
+        }
+    }
+    true
+}
+
+pub fn poll() {
+    while pump(false) {}
+}
+
+pub fn break_on_next_statement() {
+    for state in inspector().sessions.values_mut() {
+        state.session.schedule_pause_on_next_statement(
+            StringView::from(&b"debugCommand"[..]),
+            StringView::empty(),
+        );
+    }
+}
+
+pub fn wait_for_debugger() {
+    inspector().waiting_for_debugger = true;
+    while inspector().waiting_for_debugger {
+        pump(true);
+    }
+    break_on_next_statement();
+}
+
+// a session with the calling thread's engine, its messages are handled by that thread's `poll`
+pub fn connect() -> Session {
+    open_session(queue().0.clone())
+}
+
+fn open_session(events: Sender<Event>) -> Session {
+    let id = NEXT_SESSION_ID.fetch_add(1, Ordering::Relaxed);
+    let (outgoing, messages) = channel();
+    let _ = events.send(Event::Connect { id, outgoing });
+    Session {
+        id,
+        events,
+        messages,
+    }
+}
+
+pub struct Session {
+    id: u32,
+    events: Sender<Event>,
+    messages: Receiver<String>,
+}
+
+impl Session {
+    pub fn send(&self, message: impl Into<String>) {
+        let _ = self.events.send(Event::Message {
+            id: self.id,
+            message: message.into(),
+        });
+    }
+
+    pub fn recv(&self) -> Option<String> {
+        self.messages.recv().ok()
+    }
+
+    pub fn try_recv(&self) -> Option<String> {
+        self.messages.try_recv().ok()
+    }
+
+    pub fn recv_timeout(&self, timeout: Duration) -> Option<String> {
+        self.messages.recv_timeout(timeout).ok()
+    }
+}
+
+impl Drop for Session {
+    fn drop(&mut self) {
+        let _ = self.events.send(Event::Disconnect { id: self.id });
+    }
+}
+
+// serves the calling thread's engine, like `connect` its sessions are handled by `poll`
+pub fn serve(address: impl ToSocketAddrs) -> std::io::Result<SocketAddr> {
+    let listener = TcpListener::bind(address)?;
+    let address = listener.local_addr()?;
+    let events = queue().0.clone();
+    std::thread::spawn(move || {
+        for stream in listener.incoming().flatten() {
+            let events = events.clone();
+            std::thread::spawn(move || serve_connection(stream, address, events));
+        }
+    });
+    Ok(address)
+}
+
+fn serve_connection(
+    mut stream: TcpStream,
+    address: SocketAddr,
+    events: Sender<Event>,
+) -> Option<()> {
+    let mut buffer = [0; 4096];
+    let length = stream.peek(&mut buffer).ok()?;
+    let request = String::from_utf8_lossy(&buffer[..length]);
+    let path = request.split_whitespace().nth(1)?.to_owned();
+    if path.starts_with("/json") {
+        let body = if path.starts_with("/json/version") {
+            format!(
+                r#"{{"Browser": "unijs3/{}", "Protocol-Version": "1.3"}}"#,
+                env!("CARGO_PKG_VERSION")
+            )
+        } else {
+            format!(
+                r#"[{{"description": "unijs3", "devtoolsFrontendUrl": "devtools://devtools/bundled/js_app.html?experiments=true&v8only=true&ws={0}/ws", "id": "unijs3", "title": "unijs3", "type": "node", "url": "unijs3://", "webSocketDebuggerUrl": "ws://{0}/ws"}}]"#,
+                address
+            )
+        };
+        let _ = stream.read(&mut buffer);
+        write!(
+            stream,
+            "HTTP/1.1 200 OK\r\nContent-Type: application/json; charset=UTF-8\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+            body.len(),
+            body
+        )
+        .ok()?;
+        return Some(());
+    }
+    let mut websocket = tungstenite::accept(stream).ok()?;
+    websocket
+        .get_ref()
+        .set_read_timeout(Some(Duration::from_millis(10)))
+        .ok()?;
+    let session = open_session(events);
+    loop {
+        while let Some(message) = session.try_recv() {
+            websocket.send(tungstenite::Message::Text(message)).ok()?;
+        }
+        match websocket.read() {
+            Ok(tungstenite::Message::Text(message)) => session.send(message),
+            Ok(tungstenite::Message::Close(..)) => return Some(()),
+            Ok(..) => {}
+            Err(tungstenite::Error::Io(err))
+                if matches!(err.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut) => {}
+            Err(..) => return Some(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{eval, inspector, Value};
+
+    fn request(session: &inspector::Session, id: u32, method: &str, params: &str) -> String {
+        session.send(format!(
+            r#"{{"id": {}, "method": "{}", "params": {}}}"#,
+            id, method, params
+        ));
+        inspector::poll();
+        let prefix = format!(r#"{{"id":{},"#, id);
+        loop {
+            let message = session.try_recv().expect("no response");
+            if message.starts_with(&prefix) {
+                return message;
+            }
+        }
+    }
+
+    #[test]
+    fn evaluate() {
+        let session = inspector::connect();
+        let response = request(
+            &session,
+            1,
+            "Runtime.evaluate",
+            r#"{"expression": "6 * 7"}"#,
+        );
+        assert!(response.contains(r#""value":42"#), "{}", response);
+    }
+
+    #[test]
+    fn sessions_stay_with_their_engine() {
+        crate::global_set("sessionOwner", "test thread");
+        let session = inspector::connect();
+        // another thread's engine has a queue of its own
+        std::thread::spawn(inspector::poll).join().unwrap();
+        let response = request(
+            &session,
+            1,
+            "Runtime.evaluate",
+            r#"{"expression": "sessionOwner"}"#,
+        );
+        assert!(
+            response.contains(r#""value":"test thread""#),
+            "{}",
+            response
+        );
+    }
+
+    #[test]
+    fn pause_on_debugger_statement() {
+        let session = inspector::connect();
+        request(&session, 1, "Debugger.enable", "{}");
+        // the paused handler runs while v8 is blocked on the `debugger` statement
+        let (sender, receiver) = std::sync::mpsc::channel();
+        let thread_session = std::thread::spawn(move || {
+            let session = session;
+            loop {
+                let message = session.recv().unwrap();
+                if message.contains(r#""method":"Debugger.paused""#) {
+                    let _ = sender.send(message);
+                    session.send(r#"{"id": 2, "method": "Debugger.resume", "params": {}}"#);
+                    return session;
+                }
+            }
+        });
+        let function = eval("(function test(x) { const y = x + 1; debugger; return y; })")
+            .unwrap()
+            .into_function()
+            .unwrap();
+        assert_eq!(function.call([1.0.into()]).unwrap(), Value::Number(2.));
+        let paused = receiver.recv().unwrap();
+        assert!(paused.contains(r#""functionName":"test""#), "{}", paused);
+        assert!(paused.contains(r#""scopeChain""#), "{}", paused);
+        drop(thread_session.join().unwrap());
+        inspector::poll();
+    }
+}
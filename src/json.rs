@@ -1,35 +1,61 @@
 use crate::Value;
 
 pub fn stringify(value: impl Into<Value>) -> Option<String> {
-    #[cfg(not(target_arch = "wasm32"))]
+    #[cfg(engine = "v8")]
     {
         let scope = crate::v8::scope();
         let value = v8::Local::<v8::Value>::from(value.into());
         let result = v8::json::stringify(scope, value)?;
         Some(result.to_rust_string_lossy(scope))
     }
-    #[cfg(target_arch = "wasm32")]
+    #[cfg(engine = "browser")]
     {
         js_sys::JSON::stringify(&wasm_bindgen::JsValue::from(value.into())).ok()?.as_string()
     }
+    #[cfg(engine = "quickjs")]
+    {
+        let value = rquickjs::Value::from(value.into());
+        let string = crate::quickjs::ctx().json_stringify(value).ok()??;
+        string.to_string().ok()
+    }
+    #[cfg(engine = "boa")]
+    {
+        let json = crate::global_get("JSON").into_object()?;
+        let stringify = json.get("stringify").into_function()?;
+        stringify
+            .call_with(json, [value.into()])
+            .ok()?
+            .into_string()
+    }
 }
 
 pub fn parse(string: impl AsRef<str>) -> Option<Value> {
-    #[cfg(not(target_arch = "wasm32"))]
+    #[cfg(engine = "v8")]
     {
         let scope = crate::v8::scope();
         let string = v8::String::new(scope, string.as_ref())?;
         Some(Value::from(v8::json::parse(scope, string.into())?))
     }
-    #[cfg(target_arch = "wasm32")]
+    #[cfg(engine = "browser")]
     {
         Some(Value::from(js_sys::JSON::parse(string.as_ref()).ok()?))
     }
+    #[cfg(engine = "quickjs")]
+    {
+        let value = crate::quickjs::ctx().json_parse(string.as_ref()).ok()?;
+        Some(Value::from(value))
+    }
+    #[cfg(engine = "boa")]
+    {
+        let json = crate::global_get("JSON").into_object()?;
+        let parse = json.get("parse").into_function()?;
+        parse.call_with(json, [string.as_ref().into()]).ok()
+    }
 }
 
 #[cfg(test)]
 mod test {
-    #[cfg(target_arch = "wasm32")]
+    #[cfg(engine = "browser")]
     use wasm_bindgen_test::wasm_bindgen_test as test;
 
     use crate::{json, Object, Value};
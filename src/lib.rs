@@ -1,114 +1,251 @@
+// engine features aren't additive, silently picking one would switch the engine for every other
+// crate in the build
+#[cfg(all(feature = "quickjs", feature = "boa"))]
+compile_error!("the `quickjs` and `boa` features can't be enabled together");
+#[cfg(all(
+    any(feature = "quickjs", feature = "boa"),
+    feature = "v8",
+    not(target_arch = "wasm32")
+))]
+compile_error!(
+    "only one JS engine can be enabled, add `default-features = false` to use `quickjs` or `boa`"
+);
+#[cfg(all(
+    any(feature = "quickjs", feature = "boa"),
+    feature = "browser",
+    target_arch = "wasm32",
+    not(target_os = "wasi")
+))]
+compile_error!(
+    "only one JS engine can be enabled, add `default-features = false` to use `quickjs` or `boa`"
+);
+
+#[macro_use]
+mod thread_state;
 mod value;
 mod exception;
+mod engine;
+mod store;
 
 pub use value::*;
 pub use exception::*;
+pub use store::*;
+
+use engine::{Current, Engine};
+
+const HAS: &str = "(function (object, key) { return key in object; })";
 
 pub mod native;
 pub mod json;
+pub mod profiler;
+pub mod heap;
+pub mod commonjs;
+#[cfg(not(engine = "browser"))]
+pub mod sandbox;
+#[cfg(not(engine = "browser"))]
+pub mod runtime;
+#[cfg(any(engine = "v8", engine = "browser"))]
+pub mod promise;
+#[cfg(any(engine = "v8", engine = "browser"))]
+pub mod stream;
+
+#[cfg(engine = "v8")]
+pub mod event_loop;
+
+#[cfg(all(feature = "web", engine = "v8"))]
+mod web;
+
+#[cfg(all(feature = "fetch", engine = "v8"))]
+pub mod fetch;
 
-#[cfg(not(target_arch = "wasm32"))]
+#[cfg(all(feature = "inspector", engine = "v8"))]
+pub mod inspector;
+
+#[cfg(engine = "v8")]
 mod v8;
 
+#[cfg(engine = "quickjs")]
+mod quickjs;
+
+#[cfg(engine = "boa")]
+mod boa;
+
 pub fn flush() {
-    #[cfg(not(target_arch = "wasm32"))]
-    crate::v8::flush();
+    Current::flush();
 }
 
 pub fn eval(source: impl AsRef<str>) -> Result<Value, Value> {
-    #[cfg(not(target_arch = "wasm32"))]
-    {
-        let scope = crate::v8::scope();
-        let code = ::v8::String::new(scope, source.as_ref()).unwrap();
-        let script = ::v8::Script::compile(scope, code, None).unwrap();
-
-        let result = {
-            let scope = &mut ::v8::TryCatch::new(scope);
-            crate::v8::push_scope(scope);
-            if let Some(ret) = script.run(scope) {
-                Ok(Value::from(ret))
-            } else {
-                // TODO: don't unwrap
-                let exception = scope.exception().unwrap();
-                Err(Value::from(exception))
-            }
-        };
-        crate::v8::pop_scope();
-        result
-    }
-    #[cfg(target_arch = "wasm32")]
-    {
-        match js_sys::eval(source.as_ref()) {
-            Ok(value) => Ok(Value::from(value)),
-            Err(value) => Err(Value::from(value)),
-        }
-    }
+    Current::eval(source.as_ref(), None)
 }
 
-#[cfg(target_arch = "wasm32")]
-fn global_object() -> wasm_bindgen::JsValue {
-    if let Some(window) = web_sys::window() {
-        wasm_bindgen::JsValue::from(window)
-    } else if let Ok(Value::Object(global)) = eval("global") {
-        wasm_bindgen::JsValue::from(js_sys::Object::from(global))
-    } else {
-        panic!()
-    }
+// the filename shows up in stack traces and in `Function::script_location`
+pub fn eval_with_filename(
+    source: impl AsRef<str>,
+    filename: impl AsRef<str>,
+) -> Result<Value, Value> {
+    Current::eval(source.as_ref(), Some(filename.as_ref()))
 }
 
 pub fn global_set(name: impl AsRef<str>, value: impl Into<Value>) {
-    #[cfg(not(target_arch = "wasm32"))]
-    {
-        let scope = crate::v8::scope();
-        let context = crate::v8::context();
-        let global = context.global(scope);
-        let name = ::v8::String::new(scope, name.as_ref()).unwrap();
-        let value = ::v8::Local::<::v8::Value>::from(value.into());
-        global.set(scope, name.into(), value).unwrap();
-    }
-    #[cfg(target_arch = "wasm32")]
-    {
-        let global = global_object();
-        js_sys::Reflect::set(
-            &global,
-            &name.as_ref().into(),
-            &wasm_bindgen::JsValue::from(value.into()),
-        )
-        .unwrap();
-    }
+    Current::object_set(&Current::global_object(), name.as_ref(), value.into());
 }
 
 pub fn global_get(name: impl AsRef<str>) -> Value {
-    #[cfg(not(target_arch = "wasm32"))]
-    {
-        let scope = crate::v8::scope();
-        let context = crate::v8::context();
-        let global = context.global(scope);
-        let name = ::v8::String::new(scope, name.as_ref()).unwrap();
-        let value = global.get(scope, name.into());
-        if let Some(value) = value {
-            Value::from(value)
-        } else {
-            Value::Undefined
-        }
-    }
-    #[cfg(target_arch = "wasm32")]
-    {
-        let global = global_object();
-        Value::from(js_sys::Reflect::get(&global, &name.as_ref().into()).unwrap())
+    Current::object_get(&Current::global_object(), name.as_ref())
+}
+
+// `globalThis`, also in workers and other hosts without `window`
+pub fn global_object() -> Object {
+    Object {
+        object: Current::global_object(),
     }
 }
 
+// like `name in globalThis`, so inherited properties count as well
+pub fn global_has(name: impl AsRef<str>) -> bool {
+    value::helper(HAS)
+        .call([global_object().into(), name.as_ref().into()])
+        .map(|result| result.to_boolean())
+        .unwrap_or(false)
+}
+
+pub fn global_delete(name: impl AsRef<str>) {
+    Current::object_delete(&Current::global_object(), name.as_ref());
+}
+
+// the global's own enumerable names, which doesn't include `global_define`d ones
+pub fn global_keys() -> Vec<String> {
+    Current::object_keys(&Current::global_object())
+}
+
+// defines a read-only, non-enumerable and non-configurable global that scripts can't overwrite
+// or delete. Fails with the engine's TypeError when the global already has a non-configurable
+// property of that name with a different value
+pub fn global_define(name: impl AsRef<str>, value: impl Into<Value>) -> Result<(), Value> {
+    Current::global_define(name.as_ref(), value.into())
+}
+
 #[cfg(test)]
 mod test {
-    #[cfg(target_arch = "wasm32")]
+    #[cfg(engine = "browser")]
     use wasm_bindgen_test::wasm_bindgen_test as test;
 
-    use crate::{global_get, global_set, Value};
+    use crate::{
+        eval, eval_with_filename, global_define, global_delete, global_get, global_has,
+        global_keys, global_object, global_set, native, Function, Value,
+    };
 
     #[test]
     fn global_set_get() {
         global_set("test", Value::Number(636.));
         assert_eq!(global_get("test"), Value::Number(636.));
     }
+
+    #[test]
+    #[cfg(not(engine = "boa"))]
+    fn filename_in_stack() {
+        let stack = eval_with_filename("new Error('here').stack", "named.js")
+            .unwrap()
+            .into_string()
+            .unwrap();
+        assert!(stack.contains("named.js"), "{}", stack);
+    }
+
+    #[test]
+    fn global_management() {
+        assert_eq!(Value::from(global_object()), eval("globalThis").unwrap());
+        global_set("managed", true);
+        assert!(global_has("managed"));
+        assert!(global_keys().contains(&"managed".to_owned()));
+        global_delete("managed");
+        assert!(!global_has("managed"));
+        assert!(global_has("Object"));
+    }
+
+    #[test]
+    fn global_define_read_only() {
+        global_define("host", 1.).unwrap();
+        assert_eq!(
+            eval("host = 2; delete globalThis.host; host").unwrap(),
+            Value::from(1.)
+        );
+        assert!(eval("'use strict'; host = 2").is_err());
+        assert_eq!(
+            eval("Object.keys(globalThis).includes('host')").unwrap(),
+            Value::from(false)
+        );
+        global_set("preset", 1.);
+        global_define("preset", 2.).unwrap();
+        assert_eq!(
+            eval("preset = 3; delete globalThis.preset; preset").unwrap(),
+            Value::from(2.)
+        );
+        assert_eq!(
+            eval("Object.keys(globalThis).includes('preset')").unwrap(),
+            Value::from(false)
+        );
+        // the same value again is fine, anything else can't replace a locked global
+        global_define("preset", 2.).unwrap();
+        assert!(global_define("preset", 3.).is_err());
+        assert!(global_define("undefined", 1.).is_err());
+        assert_eq!(global_get("preset"), Value::from(2.));
+    }
+
+    #[test]
+    fn define_property_keeps_attributes() {
+        let object = eval("({ visible: 1 })").unwrap().into_object().unwrap();
+        object.define_property("visible", 2.);
+        assert_eq!(object.keys(), vec!["visible"]);
+        object.set("visible", 3.);
+        assert_eq!(object.get("visible"), Value::from(3.));
+        let function = Function::builder().name("named").build(|_| Ok(()));
+        assert_eq!(
+            eval("(f) => delete f.name && delete f.length")
+                .unwrap()
+                .into_function()
+                .unwrap()
+                .call([function.into()])
+                .unwrap(),
+            Value::from(true)
+        );
+    }
+
+    #[test]
+    fn internals_stay_hidden() {
+        let function = Function::new(|_| Ok(()));
+        native::wrap(0u8);
+        global_set("hostFunction", function);
+        assert_eq!(
+            eval(
+                "[
+                    ...Object.getOwnPropertyNames(globalThis)
+                        .filter((name) => /^__(staticfn_|finalization_registry|require_cache)/.test(name)),
+                    ...Object.getOwnPropertyNames(hostFunction).filter((name) => name.startsWith('__')),
+                ].join()"
+            )
+            .unwrap(),
+            Value::from("")
+        );
+    }
+
+    #[test]
+    fn native_values_stay_hidden() {
+        let wrapped = native::wrap(7u32);
+        global_set("wrapped", wrapped.clone());
+        assert_eq!(
+            eval("Reflect.ownKeys(wrapped).length").unwrap(),
+            Value::from(0.)
+        );
+        // scripts can set whatever they like, what an object wraps isn't one of its properties
+        let copy = eval("wrapped._valid = false; wrapped._data = 1; ({ ...wrapped, _valid: 1 })")
+            .unwrap()
+            .into_object()
+            .unwrap();
+        assert_eq!(native::get::<u32>(&wrapped), Some(&7));
+        assert_eq!(native::get::<u32>(&copy), None);
+        assert!(native::take::<u32>(copy).is_none());
+        assert!(native::take::<u8>(wrapped.clone()).is_none());
+        assert_eq!(native::take::<u32>(wrapped.clone()).as_deref(), Some(&7));
+        assert_eq!(native::get::<u32>(&wrapped), None);
+    }
 }
\ No newline at end of file
@@ -1,111 +1,123 @@
-use std::{any::Any, mem::{forget, transmute, MaybeUninit}};
+use std::{any::Any, collections::HashMap};
 
-use crate::{eval, global_get, global_set, Function, Object, Value};
+use crate::{eval, heap, thread_state::ThreadState, Function, Object, Value};
 
-// TODO: does this actually work in v8?
+// maps wrapper objects to ids without keeping them alive. Engines without FinalizationRegistry
+// never drop wrapped values, they live until the process exits
+const OBJECTS: &str = "(function (cleanup) {
+    const ids = new WeakMap();
+    const registry =
+        typeof FinalizationRegistry === 'function' ? new FinalizationRegistry(cleanup) : undefined;
+    return {
+        get: (object) => ids.get(object),
+        insert(object, id) {
+            ids.set(object, id);
+            registry?.register(object, id, object);
+        },
+        remove(object) {
+            ids.delete(object);
+            registry?.unregister(object);
+        },
+    };
+})";
+
+thread_state! {
+    // wrapped values stay on the Rust side, scripts only ever see an empty object
+    static NATIVES: Option<Natives> = None;
+}
 
-#[derive(Clone, Copy)]
-struct FatPointer {
-    data_pointer: *const u8,
-    vtable_pointer: *const u8,
+struct Entry {
+    value: Box<dyn Any>,
+    size: usize,
 }
 
-fn registry() -> Object {
-    let registry = if let Some(registry) = global_get("__finalization_registry").into_object() {
-        registry
-    } else {
-        let cleanup = Function::new_static(|args| {
-            let object = args.get(0).into_object().unwrap();
-            let Some(data) = object.get("_data").into_number() else {
-                return Ok(Value::Undefined);
-            };
-            let Some(vtable) = object.get("_vtable").into_number() else {
-                return Ok(Value::Undefined);
-            };
-            let fat_pointer = FatPointer {
-                data_pointer: data as usize as *mut u8,
-                vtable_pointer: vtable as usize as *mut u8,
-            };
-            unsafe {
-                let any: *mut MaybeUninit<Box<dyn Any>> = &mut transmute(fat_pointer);
-                (*any).assume_init_drop();
+struct Natives {
+    // shared by all contexts, a value wrapped in one can be read from another
+    objects: Object,
+    entries: HashMap<u32, Entry>,
+    next_id: u32,
+}
+
+fn natives() -> &'static mut Natives {
+    unsafe {
+        NATIVES.get().get_or_insert_with(|| {
+            let cleanup = Function::new_static(|args| {
+                if let Some(id) = args.get(0).into_number() {
+                    // dropped outside the table, destructors may wrap or take values too
+                    let entry = natives().entries.remove(&(id as u32));
+                    if let Some(entry) = entry {
+                        heap::adjust_external_memory(-(entry.size as i64));
+                    }
+                }
+                Ok(Value::Undefined)
+            });
+            let objects = eval(OBJECTS)
+                .unwrap()
+                .into_function()
+                .unwrap()
+                .call([cleanup.into()])
+                .unwrap()
+                .into_object()
+                .unwrap();
+            Natives {
+                objects,
+                entries: HashMap::new(),
+                next_id: 1,
             }
-            Ok(Value::Undefined)
-        });
-        let finalization_registry = eval("FinalizationRegistry").unwrap().into_function().unwrap();
-        let registry = finalization_registry.new_instance([cleanup.into()]).unwrap();
-        global_set("__finalization_registry", registry.clone());
-        registry
-    };
-    registry
+        })
+    }
 }
 
-fn add_drop(object: Object) -> Object {
-    let registry = registry();
-    let register = registry.get("register").into_function().unwrap();
-    let value = Object::new();
-    value.set("_data", object.get("_data"));
-    value.set("_vtable", object.get("_vtable"));
-    let token = Object::new();
-    register.call_with(
-        registry.clone(),
-        [object.into(), value.into(), token.clone().into()],
-    ).unwrap();
-    token
+fn call(method: &str, args: impl IntoIterator<Item = Value>) -> Value {
+    let objects = natives().objects.clone();
+    objects
+        .get(method)
+        .into_function()
+        .unwrap()
+        .call_with(objects, args)
+        .unwrap()
 }
 
-fn remove_drop(token: Object) {
-    let registry = registry();
-    let unregister = registry.get("unregister").into_function().unwrap();
-    unregister.call_with(registry.clone(), [token.into()]).unwrap();
+fn id(object: &Object) -> Option<u32> {
+    call("get", [object.clone().into()])
+        .into_number()
+        .map(|id| id as u32)
 }
 
 pub fn wrap<T: 'static>(value: T) -> Object {
-    let any: Box<dyn Any> = Box::new(value);
-    let fat_pointer = unsafe { *(&any as *const _ as *const FatPointer) };
-    forget(any);
+    wrap_with_size(value, 0)
+}
+
+// `external_size` is the Rust-side memory owned by the value, reported to the engine so that
+// script objects holding large native buffers are collected promptly
+pub fn wrap_with_size<T: 'static>(value: T, external_size: usize) -> Object {
     let object = Object::new();
-    object.set("_valid", true);
-    object.set("_data", fat_pointer.data_pointer as usize as f64);
-    object.set("_vtable", fat_pointer.vtable_pointer as usize as f64);
-    let token = add_drop(object.clone());
-    object.set("_token", token);
+    let id = natives().next_id;
+    natives().next_id += 1;
+    call("insert", [object.clone().into(), (id as f64).into()]);
+    natives().entries.insert(
+        id,
+        Entry {
+            value: Box::new(value),
+            size: external_size,
+        },
+    );
+    heap::adjust_external_memory(external_size as i64);
     object
 }
 
-pub fn get<'a, T: 'static>(object: &'a Object) -> Option<&'a T> {
-    if object.get("_valid").into_boolean().unwrap_or(false) {
-        let fat_pointer = FatPointer {
-            data_pointer: object.get("_data").into_number()? as usize as *mut u8,
-            vtable_pointer: object.get("_vtable").into_number()? as usize as *mut u8,
-        };
-        let any: *const MaybeUninit<Box<dyn Any>> = unsafe { &transmute(fat_pointer) };
-        unsafe {
-            (*any).assume_init_ref().downcast_ref::<T>()
-        }
-    } else {
-        None
-    }
+pub fn get<T: 'static>(object: &Object) -> Option<&T> {
+    let id = id(object)?;
+    natives().entries.get(&id)?.value.downcast_ref::<T>()
 }
 
 pub fn take<T: 'static>(object: Object) -> Option<Box<T>> {
-    if object.get("_valid").into_boolean().unwrap_or(false) {
-        let fat_pointer = FatPointer {
-            data_pointer: object.get("_data").into_number()? as usize as *mut u8,
-            vtable_pointer: object.get("_vtable").into_number()? as usize as *mut u8,
-        };
-        unsafe {
-            let any: *const MaybeUninit<Box<dyn Any>> = &transmute(fat_pointer);
-            if (*any).assume_init_ref().is::<T>() {
-                object.set("_valid", false);
-                remove_drop(object.get("_token").into_object().unwrap());
-                let any: MaybeUninit<Box<dyn Any>> = transmute(fat_pointer);
-                any.assume_init().downcast::<T>().ok()
-            } else {
-                None
-            }
-        }
-    } else {
-        None
+    let id = id(&object)?;
+    if !natives().entries.get(&id)?.value.is::<T>() {
+        return None;
     }
+    call("remove", [object.into()]);
+    let entry = natives().entries.remove(&id)?;
+    heap::adjust_external_memory(-(entry.size as i64));
+    entry.value.downcast::<T>().ok()
 }
@@ -0,0 +1,174 @@
+use std::path::Path;
+
+#[cfg(all(feature = "inspector", engine = "v8"))]
+use crate::thread_state::ThreadState;
+
+thread_state! {
+    #[cfg(all(feature = "inspector", engine = "v8"))]
+    static CPU_PROFILER: Option<crate::inspector::Session> = None;
+}
+
+#[derive(Debug)]
+pub enum ProfilerError {
+    Unsupported,
+    NotStarted,
+    Io(std::io::Error),
+    Protocol(String),
+}
+
+impl std::fmt::Display for ProfilerError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Self::Unsupported => write!(f, "profiling is not supported on this backend"),
+            Self::NotStarted => write!(f, "the CPU profiler was not started"),
+            Self::Io(err) => write!(f, "{}", err),
+            Self::Protocol(message) => write!(f, "inspector protocol error: {}", message),
+        }
+    }
+}
+
+impl std::error::Error for ProfilerError {}
+
+impl From<std::io::Error> for ProfilerError {
+    fn from(err: std::io::Error) -> Self {
+        Self::Io(err)
+    }
+}
+
+#[cfg(all(feature = "inspector", engine = "v8"))]
+fn request(
+    session: &crate::inspector::Session,
+    id: u32,
+    method: &str,
+) -> Result<crate::Object, ProfilerError> {
+    use crate::{json, Object, Value};
+
+    session.send(format!(
+        r#"{{"id": {}, "method": "{}", "params": {{}}}}"#,
+        id, method
+    ));
+    crate::inspector::poll();
+    while let Some(message) = session.try_recv() {
+        let Some(message) = json::parse(&message).and_then(|message| message.into_object()) else {
+            continue;
+        };
+        if message.get("id") != Value::Number(id as f64) {
+            continue;
+        }
+        if let Some(error) = message.get("error").into_object() {
+            let message = error.get("message").into_string().unwrap_or_default();
+            return Err(ProfilerError::Protocol(message));
+        }
+        return Ok(message.get("result").into_object().unwrap_or_else(Object::new));
+    }
+    Err(ProfilerError::Protocol(format!("no response to {}", method)))
+}
+
+pub fn start_cpu_profile() -> Result<(), ProfilerError> {
+    #[cfg(all(feature = "inspector", engine = "v8"))]
+    {
+        let session = crate::inspector::connect();
+        request(&session, 1, "Profiler.enable")?;
+        request(&session, 2, "Profiler.start")?;
+        unsafe {
+            *CPU_PROFILER.get() = Some(session);
+        }
+        Ok(())
+    }
+    #[cfg(not(all(feature = "inspector", engine = "v8")))]
+    {
+        Err(ProfilerError::Unsupported)
+    }
+}
+
+pub fn stop_cpu_profile() -> Result<String, ProfilerError> {
+    #[cfg(all(feature = "inspector", engine = "v8"))]
+    {
+        let session = unsafe { CPU_PROFILER.get().take() }.ok_or(ProfilerError::NotStarted)?;
+        let result = request(&session, 3, "Profiler.stop")?;
+        crate::json::stringify(result.get("profile"))
+            .ok_or_else(|| ProfilerError::Protocol("missing profile".to_owned()))
+    }
+    #[cfg(not(all(feature = "inspector", engine = "v8")))]
+    {
+        Err(ProfilerError::Unsupported)
+    }
+}
+
+pub fn write_cpu_profile(path: impl AsRef<Path>) -> Result<(), ProfilerError> {
+    let profile = stop_cpu_profile()?;
+    std::fs::write(path, profile)?;
+    Ok(())
+}
+
+pub fn heap_snapshot() -> Result<Vec<u8>, ProfilerError> {
+    #[cfg(engine = "v8")]
+    {
+        let scope = crate::v8::scope();
+        let mut snapshot = vec![];
+        scope.take_heap_snapshot(|chunk| {
+            snapshot.extend_from_slice(chunk);
+            true
+        });
+        Ok(snapshot)
+    }
+    #[cfg(not(engine = "v8"))]
+    {
+        Err(ProfilerError::Unsupported)
+    }
+}
+
+pub fn write_heap_snapshot(path: impl AsRef<Path>) -> Result<(), ProfilerError> {
+    let snapshot = heap_snapshot()?;
+    std::fs::write(path, snapshot)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    #[cfg(engine = "browser")]
+    use wasm_bindgen_test::wasm_bindgen_test as test;
+
+    use crate::profiler;
+
+    #[test]
+    fn heap_snapshot() {
+        #[cfg(engine = "v8")]
+        {
+            crate::eval("globalThis.retained = { marker: 'unijs3_heap_snapshot_marker' }").unwrap();
+            let snapshot = String::from_utf8(profiler::heap_snapshot().unwrap()).unwrap();
+            assert!(snapshot.contains("unijs3_heap_snapshot_marker"));
+            let snapshot = crate::json::parse(&snapshot).unwrap().into_object().unwrap();
+            assert!(snapshot.get("nodes").is_array());
+            assert!(snapshot.get("strings").is_array());
+        }
+        #[cfg(not(engine = "v8"))]
+        {
+            assert!(matches!(
+                profiler::heap_snapshot(),
+                Err(profiler::ProfilerError::Unsupported)
+            ));
+        }
+    }
+
+    #[test]
+    fn cpu_profile() {
+        #[cfg(all(feature = "inspector", engine = "v8"))]
+        {
+            profiler::start_cpu_profile().unwrap();
+            crate::eval("{ let total = 0; for (let i = 0; i < 1e6; i++) { total += Math.sqrt(i); } }")
+                .unwrap();
+            let profile = profiler::stop_cpu_profile().unwrap();
+            let profile = crate::json::parse(&profile).unwrap().into_object().unwrap();
+            assert!(profile.get("nodes").is_array());
+            assert!(profile.get("startTime").is_number());
+        }
+        #[cfg(not(all(feature = "inspector", engine = "v8")))]
+        {
+            assert!(matches!(
+                profiler::start_cpu_profile(),
+                Err(profiler::ProfilerError::Unsupported)
+            ));
+        }
+    }
+}
@@ -0,0 +1,145 @@
+use std::{
+    cell::RefCell,
+    future::Future,
+    pin::Pin,
+    rc::Rc,
+    task::{Context, Poll, Waker},
+};
+
+use crate::{value::helper, Function, Object, Value};
+
+const THEN: &str = "(function (value, onFulfilled, onRejected) {
+    Promise.resolve(value).then(onFulfilled, onRejected);
+})";
+const WITH_RESOLVERS: &str = "(function () {
+    let resolve, reject;
+    const promise = new Promise((a, b) => {
+        resolve = a;
+        reject = b;
+    });
+    return [promise, resolve, reject];
+})";
+
+#[derive(Default)]
+struct State {
+    result: Option<Result<Value, Value>>,
+    waker: Option<Waker>,
+}
+
+// resolves once the promise (or thenable, or plain value) settles, which needs the event loop
+// to run microtasks on native targets
+pub struct PromiseFuture {
+    state: Rc<RefCell<State>>,
+}
+
+impl Future for PromiseFuture {
+    type Output = Result<Value, Value>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let mut state = self.state.borrow_mut();
+        match state.result.take() {
+            Some(result) => Poll::Ready(result),
+            None => {
+                state.waker = Some(cx.waker().clone());
+                Poll::Pending
+            }
+        }
+    }
+}
+
+pub fn to_future(value: impl Into<Value>) -> PromiseFuture {
+    let state = Rc::new(RefCell::new(State::default()));
+    let settle = |fulfilled: bool| {
+        let state = state.clone();
+        Function::new(move |args| {
+            let mut state = state.borrow_mut();
+            let value = args.get(0);
+            state.result = Some(if fulfilled { Ok(value) } else { Err(value) });
+            if let Some(waker) = state.waker.take() {
+                waker.wake();
+            }
+            Ok(())
+        })
+    };
+    let on_fulfilled = settle(true);
+    let on_rejected = settle(false);
+    if let Err(err) = helper(THEN).call([value.into(), on_fulfilled.into(), on_rejected.into()]) {
+        state.borrow_mut().result = Some(Err(err));
+    }
+    PromiseFuture { state }
+}
+
+// the promise settles with the future's output, the future runs on the event loop (native) or the
+// browser's microtask queue (wasm32)
+pub fn from_future(future: impl Future<Output = Result<Value, Value>> + 'static) -> Object {
+    let resolvers = helper(WITH_RESOLVERS)
+        .call([])
+        .unwrap()
+        .into_array()
+        .unwrap();
+    let promise = resolvers.get(0).into_object().unwrap();
+    let resolve = resolvers.get(1).into_function().unwrap();
+    let reject = resolvers.get(2).into_function().unwrap();
+    spawn_local(async move {
+        let _ = match future.await {
+            Ok(value) => resolve.call([value]),
+            Err(err) => reject.call([err]),
+        };
+    });
+    promise
+}
+
+pub fn spawn_local(future: impl Future<Output = ()> + 'static) {
+    #[cfg(engine = "v8")]
+    crate::event_loop::spawn_local(future);
+    #[cfg(engine = "browser")]
+    wasm_bindgen_futures::spawn_local(future);
+}
+
+#[cfg(test)]
+mod test {
+    #[cfg(engine = "browser")]
+    use wasm_bindgen_test::wasm_bindgen_test as test;
+
+    #[test]
+    fn promise_to_future() {
+        #[cfg(engine = "v8")]
+        {
+            use crate::{eval, event_loop, promise, Value};
+
+            event_loop::use_virtual_time();
+            let value =
+                eval("new Promise((resolve) => setTimeout(() => resolve(42), 100))").unwrap();
+            assert_eq!(
+                event_loop::block_on(promise::to_future(value)).unwrap(),
+                Ok(Value::Number(42.))
+            );
+            let rejected = eval("Promise.reject('nope')").unwrap();
+            assert_eq!(
+                event_loop::block_on(promise::to_future(rejected)).unwrap(),
+                Err(Value::from("nope"))
+            );
+            assert_eq!(
+                event_loop::block_on(promise::to_future(7.)).unwrap(),
+                Ok(Value::Number(7.))
+            );
+        }
+    }
+
+    #[test]
+    fn future_to_promise() {
+        #[cfg(engine = "v8")]
+        {
+            use crate::{eval, event_loop, global_set, promise, Value};
+
+            let promise = promise::from_future(async {
+                let value = promise::to_future(Value::Number(1.)).await?;
+                Ok(Value::Number(value.into_number().unwrap() + 1.))
+            });
+            global_set("fromRust", promise);
+            eval("globalThis.result = null; fromRust.then((value) => result = value)").unwrap();
+            event_loop::run_until_idle().unwrap();
+            assert_eq!(eval("result").unwrap(), Value::Number(2.));
+        }
+    }
+}
@@ -0,0 +1,100 @@
+use std::mem::transmute;
+
+use rquickjs::{Context, Ctx, Runtime};
+
+use crate::thread_state::ThreadState;
+
+thread_state! {
+    static GLOBAL: Option<Global> = None;
+    static CALL_DEPTH: u32 = 0;
+    // the contexts entered on top of the global one, by sandboxes and by calls into host functions
+    static STACK: Vec<Ctx<'static>> = Vec::new();
+}
+
+// fields drop in order, the context has to go before the runtime
+struct Global {
+    ctx: Ctx<'static>,
+    context: Context,
+    runtime: Runtime,
+}
+
+unsafe fn global() -> &'static mut Global {
+    if GLOBAL.get().is_none() {
+        let runtime = Runtime::new().unwrap();
+        let context = Context::full(&runtime).unwrap();
+        // the runtime is only ever used from this thread, so the context is kept entered instead
+        // of locking it for every operation, which would fail on reentrant calls from JS
+        let ctx = context.with(|ctx| transmute::<Ctx<'_>, Ctx<'static>>(ctx.clone()));
+        *GLOBAL.get() = Some(Global {
+            ctx,
+            context,
+            runtime,
+        });
+    }
+    GLOBAL.get().as_mut().unwrap()
+}
+
+pub(crate) fn ctx() -> Ctx<'static> {
+    unsafe {
+        match STACK.get().last() {
+            Some(ctx) => ctx.clone(),
+            None => global().ctx.clone(),
+        }
+    }
+}
+
+pub(crate) fn enter_context(ctx: Ctx<'static>) {
+    unsafe { STACK.get().push(ctx) };
+}
+
+pub(crate) fn leave_context() {
+    unsafe { STACK.get().pop() };
+}
+
+// another context on the same runtime, so objects can be passed between them
+pub(crate) struct SandboxContext {
+    pub(crate) ctx: Ctx<'static>,
+    _context: Context,
+}
+
+impl SandboxContext {
+    pub(crate) fn new() -> Self {
+        let context = Context::full(runtime()).unwrap();
+        let ctx = context.with(|ctx| unsafe { transmute::<Ctx<'_>, Ctx<'static>>(ctx.clone()) });
+        Self {
+            ctx,
+            _context: context,
+        }
+    }
+}
+
+pub(crate) fn runtime() -> &'static Runtime {
+    unsafe { &global().runtime }
+}
+
+// promise reactions and FinalizationRegistry callbacks, there is no event loop so they run once
+// control returns to the top-level Rust caller
+pub(crate) fn run_pending_jobs() {
+    if unsafe { *CALL_DEPTH.get() } > 0 {
+        return;
+    }
+    while !matches!(runtime().execute_pending_job(), Ok(false)) {}
+}
+
+pub(crate) fn enter_call() {
+    unsafe { *CALL_DEPTH.get() += 1 };
+}
+
+pub(crate) fn leave_call() {
+    unsafe { *CALL_DEPTH.get() -= 1 };
+}
+
+pub fn flush() {
+    crate::value::clear_context_state();
+    unsafe {
+        let global = global();
+        let context = Context::full(&global.runtime).unwrap();
+        global.ctx = context.with(|ctx| transmute::<Ctx<'_>, Ctx<'static>>(ctx.clone()));
+        global.context = context;
+    }
+}
@@ -0,0 +1,181 @@
+use std::{
+    future::Future,
+    marker::PhantomData,
+    panic::{catch_unwind, resume_unwind, AssertUnwindSafe},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+    thread::Thread,
+};
+
+use futures::channel::oneshot;
+#[cfg(engine = "v8")]
+use futures::FutureExt;
+
+use crate::Value;
+
+type Job = Box<dyn FnOnce(&Runtime) + Send>;
+
+// the engine as seen from the runtime thread, it isn't `Send` so values can't leave `run`. The
+// crate's other functions can be used inside `run` as well
+pub struct Runtime {
+    _thread_bound: PhantomData<*const ()>,
+}
+
+impl Runtime {
+    pub fn eval(&self, source: impl AsRef<str>) -> Result<Value, Value> {
+        crate::eval(source)
+    }
+
+    pub fn eval_with_filename(
+        &self,
+        source: impl AsRef<str>,
+        filename: impl AsRef<str>,
+    ) -> Result<Value, Value> {
+        crate::eval_with_filename(source, filename)
+    }
+}
+
+#[derive(Default)]
+struct Queue {
+    jobs: Mutex<Vec<Job>>,
+    closed: AtomicBool,
+}
+
+// shared by the handles and the futures they return, the thread stops once the last one is gone
+struct Owner {
+    queue: Arc<Queue>,
+    thread: Thread,
+}
+
+impl Owner {
+    fn send(&self, job: Job) {
+        self.queue.jobs.lock().unwrap().push(job);
+        self.thread.unpark();
+    }
+}
+
+impl Drop for Owner {
+    fn drop(&mut self) {
+        self.queue.closed.store(true, Ordering::Release);
+        self.thread.unpark();
+    }
+}
+
+// a dedicated thread with its own engine that other threads send work to, so one engine can be
+// shared by threads that can't hold its values. The engine is created on that thread and, like
+// any thread's engine, is leaked when it stops
+#[derive(Clone)]
+pub struct RuntimeHandle {
+    owner: Arc<Owner>,
+}
+
+impl RuntimeHandle {
+    // every call starts another thread with a separate engine, clone the handle to share one
+    pub fn new() -> Self {
+        let queue = Arc::new(Queue::default());
+        let thread = {
+            let queue = queue.clone();
+            std::thread::Builder::new()
+                .name("unijs3".to_owned())
+                .spawn(move || run_thread(queue))
+                .unwrap()
+                .thread()
+                .clone()
+        };
+        Self {
+            owner: Arc::new(Owner { queue, thread }),
+        }
+    }
+
+    // runs `f` on the runtime thread, a panic in `f` resumes in whoever awaits the result
+    pub fn run<R: Send + 'static>(
+        &self,
+        f: impl FnOnce(&Runtime) -> R + Send + 'static,
+    ) -> impl Future<Output = R> + Send + 'static {
+        let (sender, receiver) = oneshot::channel();
+        self.owner.send(Box::new(move |runtime| {
+            let _ = sender.send(catch_unwind(AssertUnwindSafe(|| f(runtime))));
+        }));
+        output(receiver, self.owner.clone())
+    }
+
+    // like `run`, but the future `f` returns is driven by the runtime's event loop, so it can
+    // await promises, timers and fetches
+    #[cfg(engine = "v8")]
+    pub fn spawn<R, F>(
+        &self,
+        f: impl FnOnce(&Runtime) -> F + Send + 'static,
+    ) -> impl Future<Output = R> + Send + 'static
+    where
+        R: Send + 'static,
+        F: Future<Output = R> + 'static,
+    {
+        let (sender, receiver) = oneshot::channel();
+        self.owner.send(Box::new(move |runtime| {
+            let future = f(runtime);
+            crate::event_loop::spawn_local(async move {
+                let _ = sender.send(AssertUnwindSafe(future).catch_unwind().await);
+            });
+        }));
+        output(receiver, self.owner.clone())
+    }
+
+    // evaluates a script on the runtime thread. Values can't leave it, so the result comes back
+    // as JSON (`None` when it has no JSON form) and exceptions as their string form
+    pub fn eval(
+        &self,
+        source: impl Into<String>,
+    ) -> impl Future<Output = Result<Option<String>, String>> + Send + 'static {
+        let source = source.into();
+        self.run(move |runtime| match runtime.eval(source) {
+            Ok(value) => Ok(crate::json::stringify(value)),
+            Err(err) => Err(err.to_string()),
+        })
+    }
+}
+
+impl Default for RuntimeHandle {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// holds on to the runtime until the output arrives
+async fn output<R>(receiver: oneshot::Receiver<std::thread::Result<R>>, _owner: Arc<Owner>) -> R {
+    match receiver.await.expect("the runtime thread stopped") {
+        Ok(output) => output,
+        Err(panic) => resume_unwind(panic),
+    }
+}
+
+fn run_thread(queue: Arc<Queue>) {
+    let runtime = Runtime {
+        _thread_bound: PhantomData,
+    };
+    // the engine belongs to this thread, create it before the first job arrives
+    crate::global_object();
+    loop {
+        let jobs = std::mem::take(&mut *queue.jobs.lock().unwrap());
+        for job in jobs {
+            job(&runtime);
+        }
+        #[cfg(engine = "v8")]
+        {
+            // nobody is there to handle an exception thrown by a timer, the others keep running
+            let _ = crate::event_loop::tick();
+        }
+        if !queue.jobs.lock().unwrap().is_empty() {
+            continue;
+        }
+        // jobs that were sent before the last handle went away have run by now
+        if queue.closed.load(Ordering::Acquire) {
+            break;
+        }
+        #[cfg(engine = "v8")]
+        crate::event_loop::wait();
+        #[cfg(not(engine = "v8"))]
+        std::thread::park();
+    }
+}
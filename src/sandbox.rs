@@ -0,0 +1,303 @@
+use std::cell::RefCell;
+
+use crate::{
+    engine::{Contexts, Current},
+    global_define,
+    value::{replace_context_state, ContextState},
+    Object, Value,
+};
+
+// runs inside the sandbox, before the host's globals are added
+const SETUP: &str = r#"(function (allowed, tameCodeGeneration, freeze) {
+    if (tameCodeGeneration) {
+        const blocked = function Function() {
+            throw new EvalError("Code generation from strings is disabled in this sandbox");
+        };
+        Object.defineProperty(blocked, "prototype", { value: Function.prototype });
+        const functions = [function () {}, async function () {}, function* () {}, async function* () {}];
+        for (const f of functions) {
+            Object.defineProperty(Object.getPrototypeOf(f), "constructor", { value: blocked });
+        }
+        for (const name of ["eval", "Function"]) {
+            if (name in globalThis) {
+                globalThis[name] = blocked;
+            }
+        }
+    }
+    if (freeze) {
+        const frozen = new Set();
+        const visit = (value) => {
+            if ((typeof value !== "object" && typeof value !== "function") || value === null) {
+                return;
+            }
+            if (frozen.has(value)) {
+                return;
+            }
+            frozen.add(value);
+            Object.freeze(value);
+            visit(Object.getPrototypeOf(value));
+            for (const key of Reflect.ownKeys(value)) {
+                const { value: property, get, set } = Reflect.getOwnPropertyDescriptor(value, key);
+                visit(property);
+                visit(get);
+                visit(set);
+            }
+        };
+        for (const name of Object.getOwnPropertyNames(globalThis)) {
+            if (name !== "globalThis") {
+                visit(globalThis[name]);
+            }
+        }
+        // intrinsics that no global leads to
+        const generator = function* () {};
+        const asyncGenerator = async function* () {};
+        [
+            async function () {},
+            generator,
+            asyncGenerator,
+            generator(),
+            asyncGenerator(),
+            [][Symbol.iterator](),
+            ""[Symbol.iterator](),
+            new Map().entries(),
+            new Set().values(),
+            "".matchAll(/(?:)/g),
+        ].forEach(visit);
+    }
+    // last, the steps above still need the built-ins
+    if (allowed !== null) {
+        for (const name of Object.getOwnPropertyNames(globalThis)) {
+            if (!allowed.includes(name)) {
+                delete globalThis[name];
+            }
+        }
+    }
+})"#;
+
+pub struct SandboxBuilder {
+    allowed: Option<Vec<String>>,
+    code_generation: bool,
+    freeze_intrinsics: bool,
+    globals: Vec<(String, Box<dyn FnOnce() -> Value>)>,
+}
+
+impl SandboxBuilder {
+    pub fn new() -> Self {
+        Self {
+            allowed: None,
+            code_generation: true,
+            freeze_intrinsics: false,
+            globals: vec![],
+        }
+    }
+
+    // once called, the sandbox only keeps the listed globals of a fresh context, by default it
+    // keeps all of the engine's built-ins
+    pub fn allow(mut self, name: impl Into<String>) -> Self {
+        self.allowed.get_or_insert_with(Vec::new).push(name.into());
+        self
+    }
+
+    // when false, `eval`, `new Function` and the other function constructors throw an EvalError
+    pub fn code_generation(mut self, allowed: bool) -> Self {
+        self.code_generation = allowed;
+        self
+    }
+
+    // freezes the built-in constructors and prototypes so scripts can't pollute them
+    pub fn freeze_intrinsics(mut self, freeze: bool) -> Self {
+        self.freeze_intrinsics = freeze;
+        self
+    }
+
+    // adds a read-only global, `init` runs inside the sandbox so the objects and functions it
+    // creates use the sandbox's built-ins rather than the host's
+    pub fn global<V: Into<Value>>(
+        mut self,
+        name: impl Into<String>,
+        init: impl FnOnce() -> V + 'static,
+    ) -> Self {
+        self.globals
+            .push((name.into(), Box::new(move || init().into())));
+        self
+    }
+
+    pub fn build(self) -> Sandbox {
+        let sandbox = Sandbox {
+            context: Current::context_new(),
+            state: RefCell::new(ContextState::default()),
+        };
+        let tame_code_generation =
+            !self.code_generation && !Current::context_set_code_generation(&sandbox.context, false);
+        sandbox.run(|| {
+            let allowed = match self.allowed {
+                Some(allowed) => {
+                    Value::from(allowed.into_iter().map(Value::from).collect::<Vec<_>>())
+                }
+                None => Value::Null,
+            };
+            let setup = crate::eval(SETUP).unwrap().into_function().unwrap();
+            setup
+                .call([
+                    allowed,
+                    tame_code_generation.into(),
+                    self.freeze_intrinsics.into(),
+                ])
+                .unwrap();
+            for (name, init) in self.globals {
+                global_define(&name, init())
+                    .unwrap_or_else(|err| panic!("can't define the global `{}`: {}", name, err));
+            }
+        });
+        sandbox
+    }
+}
+
+impl Default for SandboxBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// a separate context with its own global object and built-ins, sharing the heap with the host so
+// values can be passed in and out
+pub struct Sandbox {
+    context: <Current as Contexts>::Context,
+    // the helpers and internals of the sandbox while it isn't running
+    state: RefCell<ContextState>,
+}
+
+impl Sandbox {
+    pub fn new() -> Self {
+        Self::builder().build()
+    }
+
+    pub fn builder() -> SandboxBuilder {
+        SandboxBuilder::new()
+    }
+
+    // everything `f` evaluates or creates belongs to the sandbox
+    pub fn run<T>(&self, f: impl FnOnce() -> T) -> T {
+        Current::context_enter(&self.context);
+        let _entered = Entered {
+            sandbox: self,
+            host: Some(replace_context_state(self.state.take())),
+        };
+        f()
+    }
+
+    pub fn eval(&self, source: impl AsRef<str>) -> Result<Value, Value> {
+        self.run(|| crate::eval(source))
+    }
+
+    pub fn eval_with_filename(
+        &self,
+        source: impl AsRef<str>,
+        filename: impl AsRef<str>,
+    ) -> Result<Value, Value> {
+        self.run(|| crate::eval_with_filename(source, filename))
+    }
+
+    pub fn global_object(&self) -> Object {
+        self.run(crate::global_object)
+    }
+}
+
+impl Default for Sandbox {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// leaves the sandbox even when `f` unwinds, so the host doesn't keep running in its context
+struct Entered<'a> {
+    sandbox: &'a Sandbox,
+    host: Option<ContextState>,
+}
+
+impl Drop for Entered<'_> {
+    fn drop(&mut self) {
+        let host = self.host.take().unwrap();
+        self.sandbox.state.replace(replace_context_state(host));
+        Current::context_leave();
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{eval, sandbox::Sandbox, Function, Value};
+
+    #[test]
+    fn separate_globals() {
+        let sandbox = Sandbox::new();
+        sandbox
+            .eval("globalThis.plugin = 1; Object.prototype.polluted = true")
+            .unwrap();
+        assert_eq!(eval("typeof plugin").unwrap(), Value::from("undefined"));
+        assert_eq!(eval("({}).polluted").unwrap(), Value::Undefined);
+        assert_eq!(sandbox.global_object().get("plugin"), Value::from(1.));
+    }
+
+    #[test]
+    fn allowlist_and_globals() {
+        let sandbox = Sandbox::builder()
+            .allow("Math")
+            .allow("Object")
+            .global("host", || {
+                Function::new(|args| Ok(format!("pong {}", args.get(0))))
+            })
+            .build();
+        assert_eq!(
+            sandbox.eval("typeof JSON + ' ' + typeof Math").unwrap(),
+            Value::from("undefined object")
+        );
+        assert_eq!(
+            sandbox.eval("host = null; host(1)").unwrap(),
+            Value::from("pong 1")
+        );
+    }
+
+    #[test]
+    fn code_generation() {
+        let sandbox = Sandbox::builder().code_generation(false).build();
+        assert!(sandbox.eval("eval('1')").is_err());
+        assert!(sandbox.eval("new Function('return 1')").is_err());
+        assert!(sandbox
+            .eval("(async function () {}).constructor('return 1')")
+            .is_err());
+        assert_eq!(sandbox.eval("1 + 1").unwrap(), Value::from(2.));
+        assert_eq!(eval("new Function('return 1')()").unwrap(), Value::from(1.));
+    }
+
+    #[test]
+    fn leaves_after_panic() {
+        let sandbox = Sandbox::new();
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            sandbox.run(|| {
+                crate::eval("globalThis.inside = 1").unwrap();
+                panic!("host closure failed");
+            })
+        }));
+        assert!(result.is_err());
+        assert_eq!(eval("typeof inside").unwrap(), Value::from("undefined"));
+        assert_eq!(sandbox.eval("inside").unwrap(), Value::from(1.));
+    }
+
+    #[test]
+    fn frozen_intrinsics() {
+        let sandbox = Sandbox::builder().freeze_intrinsics(true).build();
+        assert_eq!(
+            sandbox
+                .eval("Array.prototype.polluted = true; [].polluted")
+                .unwrap(),
+            Value::Undefined
+        );
+        assert!(sandbox
+            .eval("'use strict'; Object.prototype.polluted = true")
+            .is_err());
+        assert_eq!(
+            sandbox.eval("globalThis.own = 1; own").unwrap(),
+            Value::from(1.)
+        );
+    }
+}
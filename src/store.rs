@@ -0,0 +1,106 @@
+use std::{
+    any::{Any, TypeId},
+    cell::{Ref, RefCell, RefMut},
+    collections::HashMap,
+};
+
+use crate::thread_state::ThreadState;
+
+thread_state! {
+    // boxed, so references to a cell stay valid while other states are added or removed
+    static STATES: Option<HashMap<TypeId, Box<dyn Any>>> = None;
+}
+
+fn cell<T: 'static>() -> Option<&'static RefCell<T>> {
+    unsafe {
+        STATES
+            .get()
+            .as_ref()?
+            .get(&TypeId::of::<T>())?
+            .downcast_ref::<RefCell<T>>()
+    }
+}
+
+// state shared by all host functions of the runtime, one value per type. It outlives `flush`
+// and is reached with `Args::state` or `state` instead of being captured by every closure.
+// Returns the value it replaces
+pub fn insert_state<T: 'static>(value: T) -> Option<T> {
+    let previous = remove_state::<T>();
+    unsafe {
+        let states = STATES.get().get_or_insert_with(HashMap::new);
+        states.insert(TypeId::of::<T>(), Box::new(RefCell::new(value)));
+    }
+    previous
+}
+
+// panics while the state is borrowed
+pub fn remove_state<T: 'static>() -> Option<T> {
+    let cell = cell::<T>()?;
+    assert!(
+        cell.try_borrow_mut().is_ok(),
+        "the {} state is borrowed",
+        std::any::type_name::<T>()
+    );
+    let cell = unsafe { STATES.get().as_mut()?.remove(&TypeId::of::<T>())? }
+        .downcast::<RefCell<T>>()
+        .ok()?;
+    Some(cell.into_inner())
+}
+
+// the usual `RefCell` rules apply, e.g. this panics while `state_mut` holds the same state
+pub fn state<T: 'static>() -> Option<Ref<'static, T>> {
+    cell::<T>().map(RefCell::borrow)
+}
+
+pub fn state_mut<T: 'static>() -> Option<RefMut<'static, T>> {
+    cell::<T>().map(RefCell::borrow_mut)
+}
+
+#[cfg(test)]
+mod test {
+    #[cfg(engine = "browser")]
+    use wasm_bindgen_test::wasm_bindgen_test as test;
+
+    use crate::{eval, global_set, insert_state, remove_state, state, Function, OrThrow, Value};
+
+    struct Counter(u32);
+
+    #[test]
+    fn state_from_host_functions() {
+        insert_state(Counter(1));
+        global_set(
+            "increment",
+            Function::new(|args| {
+                let mut counter = args.state_mut::<Counter>().or_throw("no counter")?;
+                counter.0 += 1;
+                Ok(counter.0 as f64)
+            }),
+        );
+        assert_eq!(eval("increment(); increment()").unwrap(), Value::from(3.));
+        assert_eq!(state::<Counter>().unwrap().0, 3);
+        assert_eq!(insert_state(Counter(10)).unwrap().0, 3);
+        assert_eq!(remove_state::<Counter>().unwrap().0, 10);
+        assert!(state::<Counter>().is_none());
+        assert!(eval("increment()").is_err());
+    }
+
+    #[test]
+    fn borrows_survive_other_states() {
+        insert_state(Counter(7));
+        let counter = state::<Counter>().unwrap();
+        // enough types to make the table grow while the counter is borrowed
+        insert_state(1u8);
+        insert_state(1u16);
+        insert_state(1u32);
+        insert_state(1u64);
+        insert_state(1i8);
+        insert_state(1i16);
+        insert_state(1i32);
+        insert_state(1i64);
+        insert_state(String::from("state"));
+        assert_eq!(counter.0, 7);
+        drop(counter);
+        assert_eq!(remove_state::<u64>(), Some(1));
+        assert_eq!(remove_state::<Counter>().unwrap().0, 7);
+    }
+}
@@ -0,0 +1,324 @@
+use std::{
+    cell::RefCell,
+    future::Future,
+    pin::Pin,
+    rc::Rc,
+    task::{Context, Poll},
+};
+
+use futures::{Stream, StreamExt};
+
+use crate::{
+    promise::{self, PromiseFuture},
+    value::helper,
+    Function, Object, Value,
+};
+
+const ASYNC_ITERATOR: &str = "(function (pull, close) {
+    // calls to next() are queued like they are for async generators
+    let last = Promise.resolve();
+    return {
+        next() {
+            const result = last.then(() => pull()).then(([done, value]) => ({ done, value }));
+            last = result.catch(() => {});
+            return result;
+        },
+        return(value) {
+            close();
+            return Promise.resolve({ done: true, value });
+        },
+        [Symbol.asyncIterator]() {
+            return this;
+        },
+    };
+})";
+const READABLE_STREAM: &str = "(function (pull, close) {
+    if (typeof ReadableStream !== 'function') {
+        throw new TypeError('ReadableStream is not available');
+    }
+    return new ReadableStream({
+        pull(controller) {
+            return pull().then(([done, value]) => {
+                if (done) {
+                    controller.close();
+                } else {
+                    controller.enqueue(value);
+                }
+            });
+        },
+        cancel() {
+            close();
+        },
+    }, { highWaterMark: 0 });
+})";
+const GET_ASYNC_ITERATOR: &str = "(function (value) {
+    if (value !== null && value !== undefined) {
+        if (typeof value[Symbol.asyncIterator] === 'function') {
+            return value[Symbol.asyncIterator]();
+        }
+        if (typeof value.getReader === 'function') {
+            const reader = value.getReader();
+            return {
+                next: () => reader.read(),
+                return: () => reader.cancel().then(() => ({ done: true })),
+            };
+        }
+        if (typeof value[Symbol.iterator] === 'function') {
+            // like for await, values of sync iterators are awaited
+            const iterator = value[Symbol.iterator]();
+            return {
+                next() {
+                    const result = iterator.next();
+                    return Promise.resolve(result.value).then((value) => ({ done: result.done, value }));
+                },
+                return: () => iterator.return?.(),
+            };
+        }
+    }
+    throw new TypeError(`${String(value)} is not async iterable`);
+})";
+const STEP: &str = "(function (iterator) {
+    let next;
+    try {
+        next = iterator.next();
+    } catch (err) {
+        return Promise.reject(err);
+    }
+    return Promise.resolve(next).then((result) => {
+        if (Object(result) !== result) {
+            throw new TypeError(`Iterator result ${result} is not an object`);
+        }
+        return [!!result.done, result.value];
+    });
+})";
+const CLOSE: &str = "(function (iterator) {
+    if (typeof iterator.return === 'function') {
+        Promise.resolve(iterator.return()).catch(() => {});
+    }
+})";
+
+struct Source {
+    stream: Option<Pin<Box<dyn Stream<Item = Value>>>>,
+    // a pending pull holds the stream, so closing has to be remembered for when it hands it back
+    closed: bool,
+}
+
+// `pull` returns a promise for `[done, value]`, `close` drops the Rust stream
+fn source_functions(stream: impl Stream<Item = Value> + 'static) -> (Function, Function) {
+    let source = Rc::new(RefCell::new(Source {
+        stream: Some(Box::pin(stream)),
+        closed: false,
+    }));
+    let close = {
+        let source = source.clone();
+        Function::new(move |_| {
+            let mut source = source.borrow_mut();
+            source.closed = true;
+            source.stream.take();
+            Ok(())
+        })
+    };
+    let pull = Function::new(move |_| {
+        let source = source.clone();
+        Ok(promise::from_future(async move {
+            // calls are serialized on the JS side, so the stream is never borrowed twice
+            let stream = source.borrow_mut().stream.take();
+            let Some(mut stream) = stream else {
+                return Ok(Value::from(vec![true.into(), Value::Undefined]));
+            };
+            match stream.next().await {
+                Some(value) if !source.borrow().closed => {
+                    source.borrow_mut().stream = Some(stream);
+                    Ok(Value::from(vec![false.into(), value]))
+                }
+                _ => Ok(Value::from(vec![true.into(), Value::Undefined])),
+            }
+        }))
+    });
+    (pull, close)
+}
+
+// an object usable with `for await`, the stream is polled lazily as the script asks for items
+pub fn to_async_iterable(stream: impl Stream<Item = Value> + 'static) -> Object {
+    let (pull, close) = source_functions(stream);
+    helper(ASYNC_ITERATOR)
+        .call([pull.into(), close.into()])
+        .unwrap()
+        .into_object()
+        .unwrap()
+}
+
+// throws if the engine has no ReadableStream, natively it is provided by the `web` feature
+pub fn to_readable_stream(stream: impl Stream<Item = Value> + 'static) -> Result<Object, Value> {
+    let (pull, close) = source_functions(stream);
+    let readable = helper(READABLE_STREAM).call([pull.into(), close.into()])?;
+    Ok(readable.into_object().unwrap())
+}
+
+// reads a JS async iterable, ReadableStream or sync iterable, closing it if dropped early
+pub struct JsStream {
+    iterator: Value,
+    pending: Option<PromiseFuture>,
+    done: bool,
+}
+
+impl Stream for JsStream {
+    type Item = Result<Value, Value>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        if self.done {
+            return Poll::Ready(None);
+        }
+        let iterator = self.iterator.clone();
+        let pending = self
+            .pending
+            .get_or_insert_with(|| promise::to_future(helper(STEP).call([iterator]).unwrap()));
+        let result = match Pin::new(pending).poll(cx) {
+            Poll::Ready(result) => result,
+            Poll::Pending => return Poll::Pending,
+        };
+        self.pending = None;
+        match result {
+            Ok(step) => {
+                let step = step.into_array().unwrap();
+                if step.get(0).to_boolean() {
+                    self.done = true;
+                    Poll::Ready(None)
+                } else {
+                    Poll::Ready(Some(Ok(step.get(1))))
+                }
+            }
+            Err(err) => {
+                self.done = true;
+                Poll::Ready(Some(Err(err)))
+            }
+        }
+    }
+}
+
+impl Drop for JsStream {
+    fn drop(&mut self) {
+        if !self.done {
+            let _ = helper(CLOSE).call([self.iterator.clone()]);
+        }
+    }
+}
+
+pub fn from_async_iterable(value: impl Into<Value>) -> Result<JsStream, Value> {
+    let iterator = helper(GET_ASYNC_ITERATOR).call([value.into()])?;
+    Ok(JsStream {
+        iterator,
+        pending: None,
+        done: false,
+    })
+}
+
+#[cfg(test)]
+mod test {
+    #[cfg(engine = "browser")]
+    use wasm_bindgen_test::wasm_bindgen_test as test;
+
+    #[test]
+    fn stream_to_async_iterable() {
+        #[cfg(engine = "v8")]
+        {
+            use futures::{stream, StreamExt};
+
+            use crate::{eval, event_loop, global_set, stream::to_async_iterable, Value};
+
+            let lines = stream::iter(["a", "b", "c"]).map(Value::from);
+            global_set("lines", to_async_iterable(lines));
+            eval(
+                "globalThis.received = [];
+                (async () => { for await (const line of lines) received.push(line); })();",
+            )
+            .unwrap();
+            event_loop::run_until_idle().unwrap();
+            assert_eq!(eval("received.join()").unwrap(), Value::from("a,b,c"));
+        }
+    }
+
+    #[test]
+    fn stream_to_readable_stream() {
+        #[cfg(all(feature = "web", engine = "v8"))]
+        {
+            use futures::{stream, StreamExt};
+
+            use crate::{eval, event_loop, global_set, stream::to_readable_stream, Value};
+
+            let numbers = stream::iter(1..=3).map(|i| Value::Number(i as f64));
+            global_set("numbers", to_readable_stream(numbers).unwrap());
+            eval(
+                "globalThis.received = [];
+                (async () => {
+                    const reader = numbers.getReader();
+                    for (;;) {
+                        const { done, value } = await reader.read();
+                        if (done) break;
+                        received.push(value);
+                    }
+                })();",
+            )
+            .unwrap();
+            event_loop::run_until_idle().unwrap();
+            assert_eq!(eval("received.join()").unwrap(), Value::from("1,2,3"));
+        }
+    }
+
+    #[test]
+    fn close_during_pending_pull() {
+        #[cfg(engine = "v8")]
+        {
+            use futures::channel::mpsc;
+
+            use crate::{eval, event_loop, global_set, stream::to_async_iterable, Value};
+
+            let (sender, receiver) = mpsc::unbounded();
+            global_set("values", to_async_iterable(receiver));
+            eval(
+                "globalThis.results = [];
+                globalThis.iterator = values[Symbol.asyncIterator]();
+                iterator.next().then(({ done }) => results.push(done));",
+            )
+            .unwrap();
+            event_loop::run_until_idle().unwrap();
+            // the pull is waiting for the channel when the script gives up on the stream
+            eval("iterator.return()").unwrap();
+            sender.unbounded_send(Value::from(1.)).unwrap();
+            event_loop::run_until_idle().unwrap();
+            assert_eq!(eval("results.join()").unwrap(), Value::from("true"));
+            assert!(sender.is_closed());
+        }
+    }
+
+    #[test]
+    fn async_iterable_to_stream() {
+        #[cfg(engine = "v8")]
+        {
+            use futures::StreamExt;
+
+            use crate::{eval, event_loop, stream::from_async_iterable, Value};
+
+            event_loop::use_virtual_time();
+            let generator = eval(
+                "(async function* () {
+                    yield 1;
+                    await new Promise((resolve) => setTimeout(resolve, 10));
+                    yield 2;
+                    throw 'nope';
+                })()",
+            )
+            .unwrap();
+            let stream = from_async_iterable(generator).unwrap();
+            let items = event_loop::block_on(stream.collect::<Vec<_>>()).unwrap();
+            assert_eq!(
+                items,
+                vec![
+                    Ok(Value::Number(1.)),
+                    Ok(Value::Number(2.)),
+                    Err(Value::from("nope"))
+                ]
+            );
+        }
+    }
+}
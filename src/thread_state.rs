@@ -0,0 +1,28 @@
+use std::{cell::UnsafeCell, mem::ManuallyDrop, thread::LocalKey};
+
+// the engine and everything tied to it lives in thread-local state, so every thread that uses the
+// crate gets its own engine and nothing is shared between threads. The state is never dropped:
+// thread-local destructors run in no particular order, and engines can't be torn down before the
+// values that still point into them, so a thread leaks its engine when it exits
+macro_rules! thread_state {
+    ($($(#[$attr:meta])* static $name:ident: $ty:ty = $init:expr;)*) => {
+        thread_local! {
+            $(
+                $(#[$attr])*
+                static $name: std::cell::UnsafeCell<std::mem::ManuallyDrop<$ty>> =
+                    const { std::cell::UnsafeCell::new(std::mem::ManuallyDrop::new($init)) };
+            )*
+        }
+    };
+}
+
+pub(crate) trait ThreadState<T> {
+    // like a `static mut`, callers make sure the returned reference isn't aliased
+    unsafe fn get(&'static self) -> &'static mut T;
+}
+
+impl<T> ThreadState<T> for LocalKey<UnsafeCell<ManuallyDrop<T>>> {
+    unsafe fn get(&'static self) -> &'static mut T {
+        self.with(|state| &mut **state.get())
+    }
+}
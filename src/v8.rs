@@ -1,6 +1,14 @@
-use std::mem::transmute;
+use std::{mem::transmute, sync::Once};
 
-static mut GLOBAL: Option<Global> = None;
+use crate::thread_state::ThreadState;
+
+// V8 is initialized once per process, each thread then gets its own isolate
+static PLATFORM_INIT: Once = Once::new();
+static mut PLATFORM: Option<v8::SharedRef<v8::Platform>> = None;
+
+thread_state! {
+    static GLOBAL: Option<Global> = None;
+}
 
 #[derive(Default)]
 struct Global {
@@ -9,13 +17,15 @@ struct Global {
     context: Option<v8::Local<'static, v8::Context>>,
     context_scope: Option<v8::ContextScope<'static, v8::HandleScope<'static, v8::Context>>>,
     scope_stack: Vec<*mut v8::HandleScope<'static, v8::Context>>,
+    context_scopes: Vec<Box<v8::ContextScope<'static, v8::HandleScope<'static>>>>,
 }
 
 unsafe fn global() -> &'static mut Global {
-    GLOBAL.get_or_insert_with(|| {
-        let platform = v8::new_default_platform(0, false).make_shared();
-        v8::V8::initialize_platform(platform);
-        v8::V8::initialize();
+    if GLOBAL.get().is_some() {
+        return GLOBAL.get().as_mut().unwrap();
+    }
+    platform();
+    *GLOBAL.get() = Some({
         let mut global = Global::default();
         {
             let global: &'static mut Global = transmute(&mut global);
@@ -25,6 +35,7 @@ unsafe fn global() -> &'static mut Global {
                 context,
                 context_scope,
                 scope_stack,
+                context_scopes: _,
             } = global;
             *owned_isolate = Some(v8::Isolate::new(v8::CreateParams::default()));
             *handle_scope = Some(v8::HandleScope::new(owned_isolate.as_mut().unwrap()));
@@ -36,7 +47,25 @@ unsafe fn global() -> &'static mut Global {
             *scope_stack = Vec::new();
         }
         global
-    })
+    });
+    install();
+    GLOBAL.get().as_mut().unwrap()
+}
+
+pub(crate) fn platform() -> &'static v8::SharedRef<v8::Platform> {
+    PLATFORM_INIT.call_once(|| {
+        let platform = v8::new_default_platform(0, false).make_shared();
+        v8::V8::initialize_platform(platform.clone());
+        v8::V8::initialize();
+        unsafe { PLATFORM = Some(platform) };
+    });
+    // only written once, inside `call_once`
+    unsafe { PLATFORM.as_ref().unwrap() }
+}
+
+pub(crate) fn pump_platform() {
+    let scope = scope();
+    while v8::Platform::pump_message_loop(platform(), scope, false) {}
 }
 
 pub fn context() -> &'static mut v8::Local<'static, v8::Context> {
@@ -59,27 +88,56 @@ pub(crate) fn scope(
 
 pub fn push_scope(scope: &mut v8::HandleScope<'_>) {
     unsafe {
-        let global = GLOBAL.get_or_insert_with(|| Global::default());
+        let global = GLOBAL.get().get_or_insert_with(|| Global::default());
         global.scope_stack.push(transmute(scope));
     }
 }
 
 pub fn pop_scope() {
     unsafe {
-        let global = GLOBAL.get_or_insert_with(|| Global::default());
+        let global = GLOBAL.get().get_or_insert_with(|| Global::default());
         global.scope_stack.pop();
     }
 }
 
+// makes `context` the current one until `leave_context`, sandboxes use it to run in their own
+// context
+pub(crate) fn enter_context(context: &v8::Global<v8::Context>) {
+    let scope = scope();
+    let context = v8::Local::new(scope, context);
+    let context_scope = Box::new(v8::ContextScope::new(scope, context));
+    unsafe {
+        let global = global();
+        let mut context_scope: Box<v8::ContextScope<'static, v8::HandleScope<'static>>> =
+            transmute(context_scope);
+        let handle_scope: &mut v8::HandleScope<'static> = &mut context_scope;
+        global.scope_stack.push(handle_scope);
+        global.context_scopes.push(context_scope);
+    }
+}
+
+pub(crate) fn leave_context() {
+    unsafe {
+        let global = global();
+        global.scope_stack.pop();
+        global.context_scopes.pop();
+    }
+}
+
 pub fn flush() {
+    #[cfg(feature = "inspector")]
+    crate::inspector::context_destroyed();
+    crate::value::clear_context_state();
     let Global {
         owned_isolate,
         handle_scope,
         context,
         context_scope,
         scope_stack,
+        context_scopes,
     } = unsafe { global() };
     assert!(scope_stack.is_empty());
+    assert!(context_scopes.is_empty());
     *context_scope = None;
     *context = None;
     *handle_scope = None;
@@ -89,4 +147,15 @@ pub fn flush() {
         handle_scope.as_mut().unwrap(),
         *context.as_mut().unwrap(),
     ));
+    install();
+}
+
+fn install() {
+    crate::event_loop::install();
+    #[cfg(feature = "web")]
+    crate::web::install();
+    #[cfg(feature = "fetch")]
+    crate::fetch::install();
+    #[cfg(feature = "inspector")]
+    crate::inspector::context_created();
 }
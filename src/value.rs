@@ -1,11 +1,78 @@
 mod array;
+mod coerce;
 mod function;
+mod identity;
+mod inspect;
+mod iter;
 mod object;
 
 pub use array::*;
+pub use coerce::*;
 pub use function::*;
+pub use identity::*;
+pub use inspect::*;
+pub use iter::*;
 pub use object::*;
 
+use std::collections::HashMap;
+
+use crate::thread_state::ThreadState;
+
+thread_state! {
+    static HELPERS: Option<HashMap<&'static str, Function>> = None;
+    static INTERNALS: Option<HashMap<&'static str, Value>> = None;
+}
+
+// compiles a JS helper function once per context
+pub(crate) fn helper(source: &'static str) -> Function {
+    let helpers = unsafe { HELPERS.get().get_or_insert_with(HashMap::new) };
+    helpers
+        .entry(source)
+        .or_insert_with(|| crate::eval(source).unwrap().into_function().unwrap())
+        .clone()
+}
+
+// state the crate keeps per context, stored on the Rust side so scripts can't see or replace it
+pub(crate) fn internal(name: &'static str, init: impl FnOnce() -> Value) -> Value {
+    if let Some(value) =
+        unsafe { INTERNALS.get().as_ref() }.and_then(|internals| internals.get(name))
+    {
+        return value.clone();
+    }
+    let value = init();
+    set_internal(name, value.clone());
+    value
+}
+
+pub(crate) fn set_internal(name: &'static str, value: impl Into<Value>) {
+    let internals = unsafe { INTERNALS.get().get_or_insert_with(HashMap::new) };
+    internals.insert(name, value.into());
+}
+
+pub(crate) fn clear_context_state() {
+    unsafe {
+        *HELPERS.get() = None;
+        *INTERNALS.get() = None;
+    }
+}
+
+// the helpers and internals of a context that isn't the current one
+#[derive(Default)]
+pub(crate) struct ContextState {
+    helpers: Option<HashMap<&'static str, Function>>,
+    internals: Option<HashMap<&'static str, Value>>,
+}
+
+// installs another context's state, returning the current one
+pub(crate) fn replace_context_state(state: ContextState) -> ContextState {
+    unsafe {
+        ContextState {
+            helpers: std::mem::replace(HELPERS.get(), state.helpers),
+            internals: std::mem::replace(INTERNALS.get(), state.internals),
+        }
+    }
+}
+
 #[derive(Debug, Clone, PartialEq)]
 pub enum Value {
     Undefined,
@@ -150,16 +217,7 @@ impl Value {
 
 impl std::fmt::Display for Value {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
-        match self {
-            Self::Undefined => write!(f, "undefined"),
-            Self::Null => write!(f, "null"),
-            Self::Boolean(value) => write!(f, "{}", value),
-            Self::Number(value) => write!(f, "{}", value),
-            Self::String(value) => write!(f, "\"{}\"", &value),
-            Self::Array(value) => write!(f, "{}", value),
-            Self::Object(value) => write!(f, "{}", value),
-            Self::Function(value) => write!(f, "{}", value),
-        }
+        write!(f, "{}", self.inspect(&InspectOptions::default()))
     }
 }
 
@@ -217,123 +275,9 @@ impl From<Function> for Value {
     }
 }
 
-#[cfg(not(target_arch = "wasm32"))]
-impl From<v8::Local<'_, v8::Value>> for Value {
-    fn from(value: v8::Local<v8::Value>) -> Self {
-        let scope = crate::v8::scope();
-        if value.is_undefined() {
-            Self::Undefined
-        } else if value.is_null() {
-            Self::Null
-        } else if value.is_boolean() {
-            Self::Boolean(value.boolean_value(scope))
-        } else if value.is_number() {
-            Self::Number(value.number_value(scope).unwrap())
-        } else if value.is_string() {
-            // TODO: this impl kinda sucks?
-            let string: v8::Local<v8::String> = value.try_into().unwrap();
-            let mut buffer = [0; 1024];
-            let mut nchars = 0;
-            string.write_utf8(
-                scope,
-                &mut buffer,
-                Some(&mut nchars),
-                v8::WriteOptions::default(),
-            );
-            let string = std::str::from_utf8(&buffer).unwrap().to_owned();
-            Self::String(string.chars().take(nchars).collect())
-        } else if value.is_function() {
-            Self::Function(Function::from(
-                v8::Local::<v8::Function>::try_from(value).unwrap(),
-            ))
-        } else if value.is_array() {
-            // TODO: remove unwrap?
-            Self::Array(Array::from(
-                v8::Local::<v8::Array>::try_from(value).unwrap(),
-            ))
-        } else if value.is_object() {
-            // TODO: remove unwrap?
-            Self::Object(Object::from(
-                v8::Local::<v8::Object>::try_from(value).unwrap(),
-            ))
-        } else {
-            todo!("{:?}", value.to_rust_string_lossy(scope))
-        }
-    }
-}
-
-#[cfg(not(target_arch = "wasm32"))]
-impl From<Value> for v8::Local<'_, v8::Value> {
-    fn from(value: Value) -> Self {
-        let scope = crate::v8::scope();
-        match value {
-            Value::Undefined => v8::undefined(scope).into(),
-            Value::Null => v8::null(scope).into(),
-            Value::Boolean(value) => v8::Boolean::new(scope, value).into(),
-            Value::Number(value) => v8::Number::new(scope, value).into(),
-            Value::String(value) => v8::String::new(scope, value.as_str()).unwrap().into(),
-            Value::Array(value) => {
-                v8::Local::<v8::Value>::from(v8::Local::<v8::Array>::from(value))
-            }
-            Value::Object(value) => {
-                v8::Local::<v8::Value>::from(v8::Local::<v8::Object>::from(value))
-            }
-            Value::Function(value) => {
-                v8::Local::<v8::Value>::from(v8::Local::<v8::Function>::from(value))
-            }
-        }
-    }
-}
-
-#[cfg(target_arch = "wasm32")]
-impl From<wasm_bindgen::JsValue> for Value {
-    fn from(value: wasm_bindgen::JsValue) -> Self {
-        if value.is_undefined() {
-            Value::Undefined
-        } else if value.is_null() {
-            Value::Null
-        } else if let Some(value) = value.as_bool() {
-            Self::Boolean(value)
-        } else if let Some(value) = value.as_f64() {
-            Self::Number(value)
-        } else if let Some(value) = value.as_string() {
-            Self::String(value)
-        } else if value.is_function() {
-            Self::Function(Function::from(js_sys::Function::from(value)))
-        } else if value.is_array() {
-            Self::Array(Array::from(js_sys::Array::from(&value)))
-        } else if value.is_object() {
-            Self::Object(Object::from(js_sys::Object::from(value)))
-        } else {
-            // TODO: return undefined?
-            unreachable!()
-        }
-    }
-}
-
-#[cfg(target_arch = "wasm32")]
-impl From<Value> for wasm_bindgen::JsValue {
-    fn from(value: Value) -> Self {
-        match value {
-            Value::Undefined => wasm_bindgen::JsValue::undefined(),
-            Value::Null => wasm_bindgen::JsValue::null(),
-            Value::Boolean(value) => wasm_bindgen::JsValue::from_bool(value),
-            Value::Number(value) => wasm_bindgen::JsValue::from_f64(value),
-            Value::String(value) => wasm_bindgen::JsValue::from_str(&value),
-            Value::Array(value) => {
-                let array: js_sys::Array = From::<Array>::from(value);
-                let array: wasm_bindgen::JsValue = array.into();
-                array
-            }
-            Value::Object(value) => wasm_bindgen::JsValue::from(js_sys::Object::from(value)),
-            Value::Function(value) => wasm_bindgen::JsValue::from(js_sys::Function::from(value)),
-        }
-    }
-}
-
 #[cfg(test)]
 mod test {
-    #[cfg(target_arch = "wasm32")]
+    #[cfg(engine = "browser")]
     use wasm_bindgen_test::wasm_bindgen_test as test;
 
     use crate::{Array, Function, Object, Value};
@@ -436,4 +380,35 @@ mod test {
         check_equal(Value::String("hello".to_owned()), "\"hello\"");
         // TODO: Array, Object, Function
     }
+
+    #[test]
+    fn unsupported_types() {
+        // there is no variant for symbols and BigInts, passing one to Rust throws a TypeError
+        assert!(crate::eval("Symbol()").is_err());
+        assert!(crate::eval("1n").is_err());
+        assert!(crate::eval("Symbol.iterator").is_err());
+        assert!(crate::eval("throw Symbol()").is_err());
+        crate::global_set("echo", Function::new(|args| Ok(args.get(0))));
+        assert_eq!(
+            eval("try { echo(Symbol()); false } catch (err) { err instanceof TypeError }"),
+            Value::Boolean(true)
+        );
+        assert_eq!(
+            eval("try { echo(1n); false } catch (err) { err instanceof TypeError }"),
+            Value::Boolean(true)
+        );
+        // property reads can't fail, they get the wrapper object instead
+        let object = eval("({ key: Symbol.iterator, big: 2n })")
+            .into_object()
+            .unwrap();
+        let key = object.get("key");
+        assert_eq!(key.type_of(), "object");
+        let check = eval("(key, big) => [][key] === [][Symbol.iterator] && String(big + 1n)")
+            .into_function()
+            .unwrap();
+        assert_eq!(
+            check.call([key, object.get("big")]).unwrap(),
+            Value::from("3")
+        );
+    }
 }
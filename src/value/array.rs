@@ -1,11 +1,11 @@
-use crate::{AsObject, Object, Value};
+use crate::{
+    engine::{ArrayHandle, Current, Engine},
+    AsObject, Object, Value,
+};
 
 #[derive(Clone, PartialEq)]
 pub struct Array {
-    #[cfg(not(target_arch = "wasm32"))]
-    array: v8::Global<v8::Array>,
-    #[cfg(target_arch = "wasm32")]
-    array: js_sys::Array,
+    pub(crate) array: ArrayHandle,
 }
 
 impl Array {
@@ -14,97 +14,32 @@ impl Array {
     }
 
     pub fn new_with_length(length: u32) -> Self {
-        #[cfg(not(target_arch = "wasm32"))]
-        {
-            let scope = crate::v8::scope();
-            let array = v8::Array::new(scope, length as i32);
-            Self {
-                array: v8::Global::new(scope, array),
-            }
-        }
-        #[cfg(target_arch = "wasm32")]
-        {
-            Self {
-                array: js_sys::Array::new_with_length(length),
-            }
+        Self {
+            array: Current::array_new(length),
         }
     }
 
     pub fn length(&self) -> u32 {
-        #[cfg(not(target_arch = "wasm32"))]
-        {
-            let scope = crate::v8::scope();
-            let array = v8::Local::new(scope, self.array.clone());
-            array.length()
-        }
-        #[cfg(target_arch = "wasm32")]
-        {
-            self.array.length()
-        }
+        Current::array_length(&self.array)
     }
 
     pub fn get(&self, index: u32) -> Value {
-        #[cfg(not(target_arch = "wasm32"))]
-        {
-            let scope = crate::v8::scope();
-            let array = v8::Local::new(scope, self.array.clone());
-            let key = v8::Number::new(scope, index as f64);
-            if let Some(value) = array.get(scope, key.into()) {
-                Value::from(value)
-            } else {
-                Value::Undefined
-            }
-        }
-        #[cfg(target_arch = "wasm32")]
-        {
-            Value::from(self.array.get(index))
-        }
+        Current::array_get(&self.array, index)
     }
 
     pub fn set(&self, index: u32, value: impl Into<Value>) {
-        #[cfg(not(target_arch = "wasm32"))]
-        {
-            let scope = crate::v8::scope();
-            let array = v8::Local::new(scope, self.array.clone());
-            let key = v8::Number::new(scope, index as f64);
-            let value = v8::Local::<v8::Value>::from(value.into());
-            array.set(scope, key.into(), value);
-        }
-        #[cfg(target_arch = "wasm32")]
-        {
-            self.array
-                .set(index, wasm_bindgen::JsValue::from(value.into()))
-        }
+        Current::array_set(&self.array, index, value.into());
     }
 
     pub fn push(&self, value: impl Into<Value>) {
-        #[cfg(not(target_arch = "wasm32"))]
-        {
-            let scope = crate::v8::scope();
-            let array = v8::Local::new(scope, self.array.clone());
-            let length = array.length();
-            let key = v8::Local::<v8::Value>::from(v8::Number::new(scope, length as f64));
-            let value = v8::Local::<v8::Value>::from(value.into());
-            array.set(scope, key.into(), value);
-        }
-        #[cfg(target_arch = "wasm32")]
-        {
-            self.array.push(&wasm_bindgen::JsValue::from(value.into()));
-        }
+        Current::array_set(&self.array, self.length(), value.into());
     }
 }
 
 impl AsObject for Array {
     fn as_object(&self) -> Object {
-        #[cfg(not(target_arch = "wasm32"))]
-        {
-            let scope = crate::v8::scope();
-            let local = v8::Local::new(scope, self.array.clone());
-            Object::from(v8::Local::<v8::Object>::from(local))
-        }
-        #[cfg(target_arch = "wasm32")]
-        {
-            Object::from(js_sys::Object::from(self.array.clone()))
+        Object {
+            object: Current::array_as_object(&self.array),
         }
     }
 }
@@ -117,14 +52,7 @@ impl std::fmt::Debug for Array {
 
 impl std::fmt::Display for Array {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
-        write!(f, "[")?;
-        for i in 0..self.length() {
-            if i != 0 {
-                write!(f, ", ")?;
-            }
-                write!(f, "{}", self.get(i))?;
-        }
-        write!(f, "]")
+        write!(f, "{}", Value::from(self.clone()))
     }
 }
 
@@ -138,41 +66,9 @@ impl From<Vec<Value>> for Array {
     }
 }
 
-#[cfg(not(target_arch = "wasm32"))]
-impl From<v8::Local<'_, v8::Array>> for Array {
-    fn from(value: v8::Local<v8::Array>) -> Self {
-        let scope = crate::v8::scope();
-        Self {
-            array: v8::Global::new(scope, value),
-        }
-    }
-}
-
-#[cfg(not(target_arch = "wasm32"))]
-impl From<Array> for v8::Local<'_, v8::Array> {
-    fn from(value: Array) -> Self {
-        let scope = crate::v8::scope();
-        v8::Local::new(scope, &value.array)
-    }
-}
-
-#[cfg(target_arch = "wasm32")]
-impl From<js_sys::Array> for Array {
-    fn from(array: js_sys::Array) -> Self {
-        Self { array }
-    }
-}
-
-#[cfg(target_arch = "wasm32")]
-impl From<Array> for js_sys::Array {
-    fn from(array: Array) -> Self {
-        js_sys::Array::from(&array.array)
-    }
-}
-
 #[cfg(test)]
 mod test {
-    #[cfg(target_arch = "wasm32")]
+    #[cfg(engine = "browser")]
     use wasm_bindgen_test::wasm_bindgen_test as test;
 
     use crate::{Array, Object};
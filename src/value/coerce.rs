@@ -0,0 +1,250 @@
+use crate::{value::helper, Value};
+
+const LOOSE_EQUALS: &str = "(function (a, b) { return a == b; })";
+const TO_STRING: &str = "(function (value) { return `${value}`; })";
+const TO_NUMBER: &str = "(function (value) { return +value; })";
+const TO_PRIMITIVE: &str = "(function (value, hint) {
+    const isObject = (value) => value !== null && (typeof value === 'object' || typeof value === 'function');
+    if (!isObject(value)) {
+        return value;
+    }
+    const exotic = value[Symbol.toPrimitive];
+    if (exotic !== undefined && exotic !== null) {
+        if (typeof exotic !== 'function') {
+            throw new TypeError('Symbol.toPrimitive is not a function');
+        }
+        const result = exotic.call(value, hint);
+        if (isObject(result)) {
+            throw new TypeError('Cannot convert object to primitive value');
+        }
+        return result;
+    }
+    const methods = hint === 'string' ? ['toString', 'valueOf'] : ['valueOf', 'toString'];
+    for (const name of methods) {
+        const method = value[name];
+        if (typeof method === 'function') {
+            const result = method.call(value);
+            if (!isObject(result)) {
+                return result;
+            }
+        }
+    }
+    throw new TypeError('Cannot convert object to primitive value');
+})";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ToPrimitiveHint {
+    #[default]
+    Default,
+    Number,
+    String,
+}
+
+impl ToPrimitiveHint {
+    fn as_str(self) -> &'static str {
+        match self {
+            Self::Default => "default",
+            Self::Number => "number",
+            Self::String => "string",
+        }
+    }
+}
+
+impl Value {
+    // ===
+    pub fn strict_equals(&self, other: &Value) -> bool {
+        match (self, other) {
+            (Self::Number(a), Self::Number(b)) => a == b,
+            _ => self == other,
+        }
+    }
+
+    // ==
+    pub fn loose_equals(&self, other: &Value) -> Result<bool, Value> {
+        match (self, other) {
+            (Self::Undefined | Self::Null, Self::Undefined | Self::Null) => Ok(true),
+            (Self::Number(..), Self::Number(..)) | (Self::String(..), Self::String(..)) => {
+                Ok(self.strict_equals(other))
+            }
+            _ => Ok(helper(LOOSE_EQUALS)
+                .call([self.clone(), other.clone()])?
+                .to_boolean()),
+        }
+    }
+
+    // Object.is
+    pub fn same_value(&self, other: &Value) -> bool {
+        match (self, other) {
+            (Self::Number(a), Self::Number(b)) => {
+                (a.is_nan() && b.is_nan()) || a.to_bits() == b.to_bits()
+            }
+            _ => self == other,
+        }
+    }
+
+    // used by includes, Map and Set
+    pub fn same_value_zero(&self, other: &Value) -> bool {
+        match (self, other) {
+            (Self::Number(a), Self::Number(b)) => (a.is_nan() && b.is_nan()) || a == b,
+            _ => self == other,
+        }
+    }
+
+    pub fn type_of(&self) -> &'static str {
+        match self {
+            Self::Undefined => "undefined",
+            Self::Null => "object",
+            Self::Boolean(..) => "boolean",
+            Self::Number(..) => "number",
+            Self::String(..) => "string",
+            Self::Array(..) | Self::Object(..) => "object",
+            Self::Function(..) => "function",
+        }
+    }
+
+    pub fn to_boolean(&self) -> bool {
+        match self {
+            Self::Undefined | Self::Null => false,
+            Self::Boolean(value) => *value,
+            Self::Number(value) => !(value.is_nan() || *value == 0.),
+            Self::String(value) => !value.is_empty(),
+            Self::Array(..) | Self::Object(..) | Self::Function(..) => true,
+        }
+    }
+
+    pub fn to_number(&self) -> Result<f64, Value> {
+        match self {
+            Self::Undefined => Ok(f64::NAN),
+            Self::Null => Ok(0.),
+            Self::Boolean(value) => Ok(if *value { 1. } else { 0. }),
+            Self::Number(value) => Ok(*value),
+            _ => Ok(helper(TO_NUMBER)
+                .call([self.clone()])?
+                .into_number()
+                .unwrap_or(f64::NAN)),
+        }
+    }
+
+    pub fn to_js_string(&self) -> Result<String, Value> {
+        match self {
+            Self::String(value) => Ok(value.clone()),
+            _ => Ok(helper(TO_STRING)
+                .call([self.clone()])?
+                .into_string()
+                .unwrap_or_default()),
+        }
+    }
+
+    pub fn to_primitive(&self, hint: ToPrimitiveHint) -> Result<Value, Value> {
+        match self {
+            Self::Array(..) | Self::Object(..) | Self::Function(..) => {
+                helper(TO_PRIMITIVE).call([self.clone(), hint.as_str().into()])
+            }
+            _ => Ok(self.clone()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    #[cfg(engine = "browser")]
+    use wasm_bindgen_test::wasm_bindgen_test as test;
+
+    use crate::{eval, ToPrimitiveHint, Value};
+
+    fn js(source: &str) -> Value {
+        eval(source).unwrap()
+    }
+
+    #[test]
+    fn equality() {
+        let nan = Value::Number(f64::NAN);
+        let zero = Value::Number(0.);
+        let negative_zero = Value::Number(-0.);
+        assert!(!nan.strict_equals(&nan));
+        assert!(nan.same_value(&nan));
+        assert!(nan.same_value_zero(&nan));
+        assert!(zero.strict_equals(&negative_zero));
+        assert!(!zero.same_value(&negative_zero));
+        assert!(zero.same_value_zero(&negative_zero));
+
+        let object = js("({})");
+        assert!(object.strict_equals(&object.clone()));
+        assert!(!object.strict_equals(&js("({})")));
+        assert!(!js("new Number(1)").strict_equals(&Value::Number(1.)));
+    }
+
+    #[test]
+    fn loose_equality() {
+        assert!(Value::Null.loose_equals(&Value::Undefined).unwrap());
+        assert!(Value::from("1").loose_equals(&Value::Number(1.)).unwrap());
+        assert!(js("new Number(1)")
+            .loose_equals(&Value::Number(1.))
+            .unwrap());
+        assert!(js("[2]").loose_equals(&Value::from("2")).unwrap());
+        assert!(!Value::Null.loose_equals(&Value::Number(0.)).unwrap());
+        assert!(!js("({})").loose_equals(&js("({})")).unwrap());
+        assert_eq!(
+            js("({ valueOf() { throw 'nope'; } })")
+                .loose_equals(&Value::Number(1.))
+                .unwrap_err(),
+            Value::from("nope")
+        );
+    }
+
+    #[test]
+    fn coercion() {
+        assert!(!Value::Number(f64::NAN).to_boolean());
+        assert!(!Value::from("").to_boolean());
+        assert!(js("new Boolean(false)").to_boolean());
+
+        assert!(Value::Undefined.to_number().unwrap().is_nan());
+        assert_eq!(Value::from(" 42 ").to_number().unwrap(), 42.);
+        assert_eq!(js("[]").to_number().unwrap(), 0.);
+        assert_eq!(js("({ valueOf: () => 7 })").to_number().unwrap(), 7.);
+
+        assert_eq!(Value::Number(-0.).to_js_string().unwrap(), "0");
+        assert_eq!(Value::Number(1e21).to_js_string().unwrap(), "1e+21");
+        assert_eq!(js("[1, [2, 3]]").to_js_string().unwrap(), "1,2,3");
+        assert_eq!(
+            js("({ toString: () => 'custom' })").to_js_string().unwrap(),
+            "custom"
+        );
+        assert_eq!(
+            js("({ toString() { throw 'nope'; } })")
+                .to_js_string()
+                .unwrap_err(),
+            Value::from("nope")
+        );
+    }
+
+    #[test]
+    fn to_primitive() {
+        let object = js("({ valueOf: () => 1, toString: () => 'two' })");
+        assert_eq!(
+            object.to_primitive(ToPrimitiveHint::Default).unwrap(),
+            Value::Number(1.)
+        );
+        assert_eq!(
+            object.to_primitive(ToPrimitiveHint::String).unwrap(),
+            Value::from("two")
+        );
+        let exotic = js("({ [Symbol.toPrimitive]: (hint) => hint })");
+        assert_eq!(
+            exotic.to_primitive(ToPrimitiveHint::Number).unwrap(),
+            Value::from("number")
+        );
+        assert!(js("({ valueOf: () => ({}), toString: () => ({}) })")
+            .to_primitive(ToPrimitiveHint::Default)
+            .is_err());
+    }
+
+    #[test]
+    fn type_of() {
+        assert_eq!(Value::Undefined.type_of(), "undefined");
+        assert_eq!(Value::Null.type_of(), "object");
+        assert_eq!(js("[]").type_of(), "object");
+        assert_eq!(js("(() => {})").type_of(), "function");
+        assert_eq!(js("new String('a')").type_of(), "object");
+    }
+}
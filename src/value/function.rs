@@ -1,11 +1,13 @@
-use crate::{native, AsObject, Exception, Object, Value};
+use std::cell::{Ref, RefMut};
+
+use crate::{
+    engine::{Current, Engine, FunctionHandle},
+    native, AsObject, Exception, Object, Value,
+};
 
 #[derive(Clone, PartialEq)]
 pub struct Function {
-    #[cfg(not(target_arch = "wasm32"))]
-    function: v8::Global<v8::Function>,
-    #[cfg(target_arch = "wasm32")]
-    function: js_sys::Function,
+    pub(crate) function: FunctionHandle,
 }
 
 impl Function {
@@ -22,22 +24,7 @@ impl Function {
         data: impl Into<Value>,
         body: F,
     ) -> Self {
-        let body_box: Box<dyn Fn(Args) -> Result<R, Exception>> = Box::new(body);
-        let closure = native::wrap(body_box);
-        let data_arr = Value::from(vec![closure.into(), data.into()]);
-        let function = Self::new_static_with_data(data_arr, |mut args: Args| {
-            let data_arr = args.data().into_array().unwrap();
-            let closure = data_arr.get(0).into_object().unwrap();
-            let data = data_arr.get(1);
-            let closure =
-                native::get::<Box<dyn Fn(Args) -> Result<R, Exception>>>(&closure).unwrap();
-            args.data = data;
-            match (closure)(args).into() {
-                Ok(value) => Ok(value.into()),
-                Err(value) => Err(Value::from(value)),
-            }
-        });
-        function
+        FunctionBuilder::new().data(data).build(body)
     }
 
     pub fn new_static(body: fn(Args) -> Result<Value, Value>) -> Self {
@@ -48,106 +35,11 @@ impl Function {
         data: impl Into<Value>,
         body: fn(Args) -> Result<Value, Value>,
     ) -> Self {
-        #[cfg(not(target_arch = "wasm32"))]
-        {
-            let scope = crate::v8::scope();
-            let data_arr = crate::Array::new();
-            data_arr.push(body as usize as f64);
-            data_arr.push(data);
-            let function = v8::Function::builder(
-                |v8_scope: &mut v8::HandleScope<'_>,
-                 v8_args: v8::FunctionCallbackArguments<'_>,
-                 mut v8_ret: v8::ReturnValue<'_>| {
-                    crate::v8::push_scope(v8_scope);
-                    let data_arr = Value::from(v8_args.data()).into_array().unwrap();
-                    let body_ptr = data_arr.get(0).into_number().unwrap();
-                    let f: fn(Args) -> Result<Value, Value> =
-                        unsafe { std::mem::transmute(body_ptr as usize) };
-                    let data = data_arr.get(1);
-                    let this = Value::from(Object::from(v8_args.this()));
-                    let mut args = Args {
-                        this,
-                        data,
-                        args: vec![],
-                    };
-                    for i in 0..v8_args.length() {
-                        args.args.push(Value::from(v8_args.get(i)));
-                    }
-                    match f(args) {
-                        Ok(value) => {
-                            let value = v8::Local::<v8::Value>::from(value);
-                            crate::v8::pop_scope();
-                            v8_ret.set(value);
-                        }
-                        Err(err) => {
-                            v8_scope.throw_exception(v8::Local::<v8::Value>::from(err));
-                            crate::v8::pop_scope();
-                        }
-                    }
-                },
-            )
-            .data(v8::Local::<v8::Value>::from(v8::Local::<v8::Array>::from(
-                data_arr,
-            )))
-            .build(scope)
-            .unwrap();
-            Self {
-                function: v8::Global::new(scope, function),
-            }
-        }
-        #[cfg(target_arch = "wasm32")]
-        {
-            let id = format!("__staticfn_{}", body as usize);
-            let inner_function = if let Value::Function(function) = crate::global_get(&id) {
-                function
-            } else {
-                let function = {
-                    use wasm_bindgen::{closure::Closure, JsValue};
-                    let bindgen_closure = Closure::<
-                        dyn Fn(JsValue, JsValue, JsValue) -> Result<JsValue, JsValue>,
-                    >::new(
-                        move |js_this: JsValue, js_data: JsValue, js_args: JsValue| {
-                            let this = Value::from(js_this);
-                            let data = Value::from(js_data);
-                            let mut args = Args {
-                                this,
-                                data,
-                                args: vec![],
-                            };
-                            let js_args_array: js_sys::Array = js_args.into();
-                            for i in 0..js_args_array.length() {
-                                args.args.push(Value::from(js_args_array.get(i)));
-                            }
-                            match body(args) {
-                                Ok(value) => Ok(JsValue::from(value)),
-                                Err(err) => Err(JsValue::from(err)),
-                            }
-                        },
-                    );
-                    let closure = Value::from(JsValue::from(bindgen_closure.as_ref()))
-                        .into_function()
-                        .unwrap();
-                    bindgen_closure.forget();
-                    closure
-                };
-                crate::global_set(&id, function.clone());
-                function
-            };
-            let js_wrapper = r#"function wrapper() {
-                return wrapper.__fn.apply(null, [this, wrapper.__data, Array.from(arguments)]);
-            }"#;
-            // TODO: add define_property to Object
-            let function = crate::eval(&format!(
-                "{}; Object.defineProperty(wrapper, \"name\", {{ value: \"\" }}); wrapper",
-                js_wrapper
-            ))
-            .unwrap()
-            .into_function()
-            .unwrap();
-            function.as_object().set("__fn", inner_function);
-            function.as_object().set("__data", data);
-            function
-        }
+        FunctionBuilder::new().data(data).build_static(body)
+    }
+
+    pub fn builder() -> FunctionBuilder {
+        FunctionBuilder::new()
     }
 
     pub fn call(&self, args: impl IntoIterator<Item = Value>) -> Result<Value, Value> {
@@ -159,153 +51,202 @@ impl Function {
         receiver: impl Into<Value>,
         args: impl IntoIterator<Item = Value>,
     ) -> Result<Value, Value> {
-        #[cfg(not(target_arch = "wasm32"))]
-        {
-            let scope = crate::v8::scope();
-            let function = v8::Local::new(scope, self.function.clone());
-            let receiver = v8::Local::<v8::Value>::from(receiver.into());
-            let args = args
-                .into_iter()
-                .map(|value| v8::Local::<v8::Value>::from(value))
-                .collect::<Vec<_>>();
-            let result = {
-                let scope = &mut v8::TryCatch::new(scope);
-                crate::v8::push_scope(scope);
-                if let Some(ret) = function.call(scope, receiver, &args) {
-                    Ok(Value::from(ret))
-                } else {
-                    // TODO: don't unwrap
-                    let exception = scope.exception().unwrap();
-                    Err(Value::from(exception))
-                }
-            };
-            crate::v8::pop_scope();
-            result
-        }
-        #[cfg(target_arch = "wasm32")]
-        {
-            let receiver = wasm_bindgen::JsValue::from(receiver.into());
-            let array = js_sys::Array::new();
-            for arg in args {
-                array.push(&wasm_bindgen::JsValue::from(arg));
-            }
-            match self.function.apply(&receiver, &array) {
-                Ok(value) => Ok(Value::from(value)),
-                Err(value) => Err(Value::from(value)),
-            }
-        }
+        Current::function_call(&self.function, receiver.into(), args.into_iter().collect())
     }
 
     pub fn new_instance(&self, args: impl IntoIterator<Item = Value>) -> Result<Object, Value> {
-        #[cfg(not(target_arch = "wasm32"))]
-        {
-            let scope = crate::v8::scope();
-            let function = v8::Local::new(scope, self.function.clone());
-            let args = args
-                .into_iter()
-                .map(|value| v8::Local::<v8::Value>::from(value))
-                .collect::<Vec<_>>();
-            let result = {
-                let scope = &mut v8::TryCatch::new(scope);
-                crate::v8::push_scope(scope);
-                if let Some(ret) = function.new_instance(scope, &args) {
-                    Ok(Object::from(ret))
-                } else {
-                    // TODO: don't unwrap
-                    let exception = scope.exception().unwrap();
-                    Err(Value::from(exception))
-                }
-            };
-            crate::v8::pop_scope();
-            result
-        }
-        #[cfg(target_arch = "wasm32")]
-        {
-            let array = js_sys::Array::new();
-            for arg in args {
-                array.push(&wasm_bindgen::JsValue::from(arg));
-            }
-            match js_sys::Reflect::construct(&self.function, &array) {
-                Ok(object) => Ok(Object::from(js_sys::Object::from(object))),
-                Err(err) => Err(Value::from(err)),
-            }
-        }
+        Current::function_new_instance(&self.function, args.into_iter().collect())
     }
 }
 
-impl AsObject for Function {
-    fn as_object(&self) -> Object {
-        #[cfg(not(target_arch = "wasm32"))]
-        {
-            let scope = crate::v8::scope();
-            let local = v8::Local::new(scope, self.function.clone());
-            Object::from(v8::Local::<v8::Object>::from(local))
+pub struct FunctionBuilder {
+    pub(crate) name: String,
+    pub(crate) length: u32,
+    pub(crate) constructor: bool,
+    pub(crate) side_effect_free: bool,
+    pub(crate) data: Value,
+}
+
+impl FunctionBuilder {
+    pub fn new() -> Self {
+        Self {
+            name: String::new(),
+            length: 0,
+            constructor: true,
+            side_effect_free: false,
+            data: Value::Undefined,
         }
-        #[cfg(target_arch = "wasm32")]
-        {
-            Object::from(js_sys::Object::from(self.function.clone()))
+    }
+
+    pub fn name(mut self, name: impl Into<String>) -> Self {
+        self.name = name.into();
+        self
+    }
+
+    pub fn length(mut self, length: u32) -> Self {
+        self.length = length;
+        self
+    }
+
+    // when false, calling the function with `new` throws a TypeError
+    pub fn constructor(mut self, constructor: bool) -> Self {
+        self.constructor = constructor;
+        self
+    }
+
+    // lets the inspector call the function while evaluating previews, e.g. for getters
+    pub fn side_effect_free(mut self, side_effect_free: bool) -> Self {
+        self.side_effect_free = side_effect_free;
+        self
+    }
+
+    pub fn data(mut self, data: impl Into<Value>) -> Self {
+        self.data = data.into();
+        self
+    }
+
+    pub fn build<F: Fn(Args) -> Result<R, Exception> + 'static, R: Into<Value> + 'static>(
+        self,
+        body: F,
+    ) -> Function {
+        let body_box: Box<dyn Fn(Args) -> Result<R, Exception>> = Box::new(body);
+        let closure = native::wrap(body_box);
+        let data_arr = Value::from(vec![closure.into(), self.data.clone()]);
+        self.data(data_arr).build_static(|mut args: Args| {
+            let data_arr = args.data().into_array().unwrap();
+            let closure = data_arr.get(0).into_object().unwrap();
+            let data = data_arr.get(1);
+            let closure =
+                native::get::<Box<dyn Fn(Args) -> Result<R, Exception>>>(&closure).unwrap();
+            args.data = data;
+            match (closure)(args).into() {
+                Ok(value) => Ok(value.into()),
+                Err(value) => Err(Value::from(value)),
+            }
+        })
+    }
+
+    pub fn build_static(self, body: fn(Args) -> Result<Value, Value>) -> Function {
+        Function {
+            function: Current::function_new(self, body),
         }
     }
 }
 
-impl std::fmt::Debug for Function {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{}", self)
-    }
+// panics must not unwind through the engine's frames, so they are rethrown as JS errors
+pub(crate) fn call_body(
+    body: fn(Args) -> Result<Value, Value>,
+    args: Args,
+) -> Result<Value, Value> {
+    catch_panic(|| body(args))
 }
 
-impl std::fmt::Display for Function {
-    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
-        let name = self
-            .as_object()
-            .get("name")
-            .into_string()
-            .unwrap_or_else(|| String::new());
-        if name.is_empty() {
-            write!(f, "ƒ()")
-        } else {
-            write!(f, "{}()", name)
-        }
+pub(crate) fn catch_panic(f: impl FnOnce() -> Result<Value, Value>) -> Result<Value, Value> {
+    std::panic::catch_unwind(std::panic::AssertUnwindSafe(f))
+        .unwrap_or_else(|payload| Err(Exception::from_panic(payload).into()))
+}
+
+impl Default for FunctionBuilder {
+    fn default() -> Self {
+        Self::new()
     }
 }
 
-#[cfg(not(target_arch = "wasm32"))]
-impl From<v8::Local<'_, v8::Function>> for Function {
-    fn from(value: v8::Local<v8::Function>) -> Self {
-        let scope = crate::v8::scope();
-        Self {
-            function: v8::Global::new(scope, value),
-        }
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ScriptLocation {
+    pub file: Option<String>,
+    // 1-based, like stack traces
+    pub line: u32,
+    pub column: u32,
+}
+
+const SOURCE_TEXT: &str = "(function (f) { return Function.prototype.toString.call(f); })";
+const IS_CONSTRUCTOR: &str = "(function (f) {
+    try {
+        Reflect.construct(String, [], f);
+        return true;
+    } catch {
+        return false;
+    }
+})";
+const BIND: &str = "(function (f, thisArg, args) { return f.bind(thisArg, ...args); })";
+
+impl Function {
+    pub fn name(&self) -> String {
+        Current::function_name(&self.function)
+    }
+
+    pub fn length(&self) -> u32 {
+        self.as_object().get("length").into_number().unwrap_or(0.) as u32
+    }
+
+    pub fn source_text(&self) -> String {
+        crate::value::helper(SOURCE_TEXT)
+            .call([self.clone().into()])
+            .ok()
+            .and_then(|source| source.into_string())
+            .unwrap_or_default()
+    }
+
+    // checks [[Construct]] without calling the function
+    pub fn is_constructor(&self) -> bool {
+        crate::value::helper(IS_CONSTRUCTOR)
+            .call([self.clone().into()])
+            .map(|result| result.to_boolean())
+            .unwrap_or(false)
+    }
+
+    pub fn is_async(&self) -> bool {
+        Current::function_is_async(&self.function)
+    }
+
+    pub fn is_generator(&self) -> bool {
+        Current::function_is_generator(&self.function)
+    }
+
+    pub fn bind(
+        &self,
+        this: impl Into<Value>,
+        args: impl IntoIterator<Item = Value>,
+    ) -> Result<Function, Value> {
+        let args = Value::from(args.into_iter().collect::<Vec<_>>());
+        let bound = crate::value::helper(BIND).call([self.clone().into(), this.into(), args])?;
+        Ok(bound.into_function().unwrap())
+    }
+
+    // None for native and bound functions, and with the browser engine which doesn't expose it
+    pub fn script_location(&self) -> Option<ScriptLocation> {
+        Current::function_script_location(&self.function)
     }
 }
 
-#[cfg(not(target_arch = "wasm32"))]
-impl From<Function> for v8::Local<'_, v8::Function> {
-    fn from(value: Function) -> Self {
-        let scope = crate::v8::scope();
-        v8::Local::new(scope, &value.function)
+impl AsObject for Function {
+    fn as_object(&self) -> Object {
+        Object {
+            object: Current::function_as_object(&self.function),
+        }
     }
 }
 
-#[cfg(target_arch = "wasm32")]
-impl From<js_sys::Function> for Function {
-    fn from(function: js_sys::Function) -> Self {
-        Self { function }
+impl std::fmt::Debug for Function {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self)
     }
 }
 
-#[cfg(target_arch = "wasm32")]
-impl From<Function> for js_sys::Function {
-    fn from(function: Function) -> Self {
-        js_sys::Function::from(function.function)
+impl std::fmt::Display for Function {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}", Value::from(self.clone()))
     }
 }
 
 #[derive(Debug, Clone)]
 pub struct Args {
-    this: Value,
-    data: Value,
-    args: Vec<Value>,
+    pub(crate) this: Value,
+    pub(crate) holder: Value,
+    pub(crate) new_target: Value,
+    pub(crate) data: Value,
+    pub(crate) args: Vec<Value>,
 }
 
 impl Args {
@@ -335,14 +276,198 @@ impl Args {
     pub fn length(&self) -> u32 {
         self.args.len() as u32
     }
+
+    pub fn as_slice(&self) -> &[Value] {
+        &self.args
+    }
+
+    pub fn iter(&self) -> std::slice::Iter<'_, Value> {
+        self.args.iter()
+    }
+
+    // the arguments from `from` onwards, like `...rest` parameters
+    pub fn rest(&self, from: u32) -> &[Value] {
+        self.args.get(from as usize..).unwrap_or_default()
+    }
+
+    // the object the function was found on, same as `this` except for accessors on prototypes
+    // natively, always `this` on wasm32
+    pub fn holder(&self) -> Value {
+        self.holder.clone()
+    }
+
+    // `new.target`, undefined unless the function was called with `new`
+    pub fn new_target(&self) -> Value {
+        self.new_target.clone()
+    }
+
+    // when true, `this` is the newly allocated instance and is what `new` evaluates to unless the
+    // function returns another object
+    pub fn is_construct_call(&self) -> bool {
+        !matches!(self.new_target, Value::Undefined)
+    }
+
+    // the value a constructor returns to hand back the instance it initialized
+    pub fn into_this(self) -> Value {
+        self.this
+    }
+
+    // the runtime's state of type `T`, see `insert_state`
+    pub fn state<T: 'static>(&self) -> Option<Ref<'_, T>> {
+        crate::state::<T>()
+    }
+
+    pub fn state_mut<T: 'static>(&self) -> Option<RefMut<'_, T>> {
+        crate::state_mut::<T>()
+    }
+}
+
+impl<'a> IntoIterator for &'a Args {
+    type Item = &'a Value;
+    type IntoIter = std::slice::Iter<'a, Value>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.args.iter()
+    }
 }
 
 #[cfg(test)]
 mod test {
-    #[cfg(target_arch = "wasm32")]
+    #[cfg(engine = "browser")]
     use wasm_bindgen_test::wasm_bindgen_test as test;
 
-    use crate::{eval, Function, Value};
+    use crate::{eval, Exception, Function, Value};
+
+    #[test]
+    fn introspection() {
+        let function = eval("(function add(a, b) { return a + b; })")
+            .unwrap()
+            .into_function()
+            .unwrap();
+        assert_eq!(function.name(), "add");
+        assert_eq!(function.length(), 2);
+        assert_eq!(
+            function.source_text(),
+            "function add(a, b) { return a + b; }"
+        );
+        assert!(function.is_constructor());
+        assert!(!function.is_async());
+        assert!(!function.is_generator());
+
+        let arrow = eval("(async () => {})").unwrap().into_function().unwrap();
+        assert!(arrow.is_async());
+        assert!(!arrow.is_constructor());
+        let generator = eval("(function* numbers() {})")
+            .unwrap()
+            .into_function()
+            .unwrap();
+        assert!(generator.is_generator());
+        assert!(!generator.is_constructor());
+        assert!(!eval("({ method() {} }).method")
+            .unwrap()
+            .into_function()
+            .unwrap()
+            .is_constructor());
+    }
+
+    #[test]
+    fn builder() {
+        let function = Function::builder()
+            .name("greet")
+            .length(1)
+            .constructor(false)
+            .side_effect_free(true)
+            .build(|args| Ok(format!("hello {}", args.get(0).into_string().unwrap())));
+        assert_eq!(function.name(), "greet");
+        assert_eq!(function.length(), 1);
+        assert!(!function.is_constructor());
+        assert!(function.source_text().contains("greet"));
+        assert_eq!(
+            function.call(["Bob".into()]).unwrap(),
+            Value::from("hello Bob")
+        );
+        assert!(function.new_instance([]).is_err());
+
+        crate::global_set("greet", function);
+        assert_eq!(
+            eval("try { new greet(); } catch (err) { err instanceof TypeError }").unwrap(),
+            Value::Boolean(true)
+        );
+
+        let anonymous = Function::new(|_| Ok(()));
+        assert_eq!(anonymous.name(), "");
+        assert!(anonymous.is_constructor());
+    }
+
+    #[test]
+    fn args() {
+        let sum = Function::new(|args| {
+            let rest: f64 = args.rest(1).iter().map(|v| v.as_number().unwrap()).sum();
+            assert_eq!(args.as_slice().len(), args.length() as usize);
+            assert_eq!(args.iter().count(), args.length() as usize);
+            Ok(format!("{}:{}", args.get(0).into_string().unwrap(), rest))
+        });
+        assert_eq!(
+            sum.call(["total".into(), 1.0.into(), 2.0.into()]).unwrap(),
+            Value::from("total:3")
+        );
+        assert_eq!(sum.call(["none".into()]).unwrap(), Value::from("none:0"));
+
+        let point = Function::new(|args| {
+            if !args.is_construct_call() {
+                return Err(Exception::msg("Point must be called with new"));
+            }
+            let this = args.this().into_object().unwrap();
+            this.set("x", args.get(0));
+            this.set("target", args.new_target());
+            Ok(args.into_this())
+        });
+        crate::global_set("Point", point);
+        assert_eq!(eval("new Point(1).x").unwrap(), Value::Number(1.));
+        assert_eq!(
+            eval("new Point(1).target === Point").unwrap(),
+            Value::Boolean(true)
+        );
+        assert_eq!(
+            eval("class Sub extends Point {}; new Sub(2) instanceof Sub").unwrap(),
+            Value::Boolean(true)
+        );
+        assert_eq!(
+            eval("try { Point(1) } catch (err) { err }").unwrap(),
+            Value::from("Point must be called with new")
+        );
+    }
+
+    #[test]
+    fn bind() {
+        let function = eval("(function (a, b) { return [this.x, a, b].join(); })")
+            .unwrap()
+            .into_function()
+            .unwrap();
+        let this = crate::Object::new();
+        this.set("x", 1.);
+        let bound = function.bind(this, [2.0.into()]).unwrap();
+        assert_eq!(bound.length(), 1);
+        assert_eq!(bound.call([3.0.into()]).unwrap(), Value::from("1,2,3"));
+    }
+
+    #[test]
+    fn script_location() {
+        #[cfg(engine = "v8")]
+        {
+            let function = crate::eval_with_filename(
+                "(function () {\n  function handler() {}\n  return handler;\n})()",
+                "handlers.js",
+            )
+            .unwrap()
+            .into_function()
+            .unwrap();
+            let location = function.script_location().unwrap();
+            assert_eq!(location.file.as_deref(), Some("handlers.js"));
+            assert_eq!(location.line, 2);
+            assert_eq!(location.column, 19);
+        }
+    }
 
     #[test]
     fn call_static_function() {
@@ -0,0 +1,254 @@
+use std::{
+    collections::HashMap,
+    hash::{Hash, Hasher},
+};
+
+use crate::{value::helper, Array, AsObject, Function, Object, Value};
+
+#[cfg(not(engine = "v8"))]
+const IDENTITY_HASH: &str = "(function () {
+    const ids = new WeakMap();
+    let next = 1;
+    return function (object) {
+        let id = ids.get(object);
+        if (id === undefined) {
+            id = next++;
+            ids.set(object, id);
+        }
+        return id;
+    };
+})()";
+
+const IDENTITY_MAP: &str = "(function () {
+    const ids = new WeakMap();
+    const collected = [];
+    const registry = new FinalizationRegistry((id) => collected.push(id));
+    return {
+        get: (key) => ids.get(key),
+        insert(key, id) {
+            ids.set(key, id);
+            registry.register(key, id, key);
+        },
+        remove(key) {
+            const id = ids.get(key);
+            ids.delete(key);
+            registry.unregister(key);
+            return id;
+        },
+        collected: () => collected.splice(0),
+    };
+})";
+
+impl Object {
+    // stable for the lifetime of the object, but not unique
+    pub fn identity_hash(&self) -> i32 {
+        #[cfg(engine = "v8")]
+        {
+            v8::Local::<v8::Object>::from(self.clone())
+                .get_identity_hash()
+                .get()
+        }
+        #[cfg(not(engine = "v8"))]
+        {
+            helper(IDENTITY_HASH)
+                .call([self.clone().into()])
+                .ok()
+                .and_then(|id| id.into_number())
+                .unwrap_or(0.) as i32
+        }
+    }
+}
+
+impl Eq for Object {}
+
+impl Hash for Object {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.identity_hash().hash(state);
+    }
+}
+
+impl Eq for Array {}
+
+impl Hash for Array {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.as_object().identity_hash().hash(state);
+    }
+}
+
+impl Eq for Function {}
+
+impl Hash for Function {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.as_object().identity_hash().hash(state);
+    }
+}
+
+// maps objects to Rust values without keeping the objects alive, entries are dropped once their
+// key has been collected. Values that hold a handle to their own key will keep it alive.
+pub struct IdentityMap<V> {
+    keys: Object,
+    values: HashMap<u32, V>,
+    next_id: u32,
+}
+
+impl<V> IdentityMap<V> {
+    pub fn new() -> Self {
+        let keys = helper(IDENTITY_MAP)
+            .call([])
+            .unwrap()
+            .into_object()
+            .unwrap();
+        Self {
+            keys,
+            values: HashMap::new(),
+            next_id: 1,
+        }
+    }
+
+    fn call(&self, method: &str, args: impl IntoIterator<Item = Value>) -> Value {
+        self.keys
+            .get(method)
+            .into_function()
+            .unwrap()
+            .call_with(self.keys.clone(), args)
+            .unwrap()
+    }
+
+    fn id(&self, key: &impl AsObject) -> Option<u32> {
+        self.call("get", [key.as_object().into()])
+            .into_number()
+            .map(|id| id as u32)
+    }
+
+    // drops the values whose keys have been collected
+    pub fn purge(&mut self) {
+        if let Some(collected) = self.call("collected", []).into_array() {
+            for i in 0..collected.length() {
+                if let Some(id) = collected.get(i).into_number() {
+                    self.values.remove(&(id as u32));
+                }
+            }
+        }
+    }
+
+    pub fn insert(&mut self, key: &impl AsObject, value: V) -> Option<V> {
+        self.purge();
+        if let Some(id) = self.id(key) {
+            return self.values.insert(id, value);
+        }
+        let id = self.next_id;
+        self.next_id += 1;
+        self.call("insert", [key.as_object().into(), (id as f64).into()]);
+        self.values.insert(id, value);
+        None
+    }
+
+    pub fn get(&self, key: &impl AsObject) -> Option<&V> {
+        self.values.get(&self.id(key)?)
+    }
+
+    pub fn get_mut(&mut self, key: &impl AsObject) -> Option<&mut V> {
+        let id = self.id(key)?;
+        self.values.get_mut(&id)
+    }
+
+    pub fn contains_key(&self, key: &impl AsObject) -> bool {
+        self.get(key).is_some()
+    }
+
+    pub fn remove(&mut self, key: &impl AsObject) -> Option<V> {
+        self.purge();
+        let id = self
+            .call("remove", [key.as_object().into()])
+            .into_number()?;
+        self.values.remove(&(id as u32))
+    }
+
+    pub fn len(&mut self) -> usize {
+        self.purge();
+        self.values.len()
+    }
+
+    pub fn is_empty(&mut self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl<V> Default for IdentityMap<V> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    #[cfg(engine = "browser")]
+    use wasm_bindgen_test::wasm_bindgen_test as test;
+
+    use std::collections::HashMap;
+
+    use crate::{eval, AsObject, Function, IdentityMap, Object};
+
+    #[test]
+    fn hash_map_keys() {
+        let a = Object::new();
+        let b = Object::new();
+        let mut entities = HashMap::new();
+        entities.insert(a.clone(), 1);
+        entities.insert(b.clone(), 2);
+        let same = eval("(function (object) { return object; })")
+            .unwrap()
+            .into_function()
+            .unwrap()
+            .call([a.clone().into()])
+            .unwrap()
+            .into_object()
+            .unwrap();
+        assert_eq!(entities.get(&same), Some(&1));
+        assert_eq!(entities.get(&b), Some(&2));
+        assert_eq!(entities.get(&Object::new()), None);
+        assert_eq!(a.identity_hash(), same.identity_hash());
+
+        let function = Function::new(|_| Ok(()));
+        let mut functions = HashMap::new();
+        functions.insert(function.clone(), "function");
+        assert_eq!(functions.get(&function), Some(&"function"));
+    }
+
+    #[test]
+    fn identity_map() {
+        let mut map = IdentityMap::new();
+        let key = Object::new();
+        let function = Function::new(|_| Ok(()));
+        assert_eq!(map.insert(&key, 1), None);
+        assert_eq!(map.insert(&key, 2), Some(1));
+        map.insert(&function, 3);
+        assert_eq!(map.get(&key), Some(&2));
+        assert_eq!(map.get(&function.as_object()), Some(&3));
+        assert!(!map.contains_key(&Object::new()));
+        assert_eq!(map.len(), 2);
+        assert_eq!(map.remove(&key), Some(2));
+        assert_eq!(map.get(&key), None);
+        assert_eq!(map.len(), 1);
+    }
+
+    #[test]
+    fn identity_map_weak_keys() {
+        #[cfg(engine = "v8")]
+        {
+            use std::{cell::RefCell, rc::Rc};
+
+            let map = Rc::new(RefCell::new(IdentityMap::new()));
+            let inner = map.clone();
+            // the key only lives in the callback's handle scope
+            let insert = Function::new(move |_| {
+                inner.borrow_mut().insert(&Object::new(), "value");
+                Ok(())
+            });
+            insert.call([]).unwrap();
+            assert_eq!(map.borrow_mut().len(), 1);
+            crate::heap::collect_garbage();
+            assert_eq!(map.borrow_mut().len(), 0);
+        }
+    }
+}
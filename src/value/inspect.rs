@@ -0,0 +1,417 @@
+use crate::{value::helper, Array, AsObject, Function, Object, Value};
+
+const DESCRIBE: &str = "(function (value) {
+    const prototype = Object.getPrototypeOf(value);
+    let name = null;
+    if (prototype !== null) {
+        const constructor = Object.getOwnPropertyDescriptor(prototype, 'constructor');
+        name = constructor && typeof constructor.value === 'function' ? constructor.value.name : '';
+    }
+    return [value instanceof Error, name];
+})";
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct InspectOptions {
+    // how many levels of nested objects are printed, None for unlimited
+    pub depth: Option<usize>,
+    // how many array items are printed before truncating, None for unlimited
+    pub max_array_length: Option<usize>,
+    // entries are split onto separate lines when the single line form is longer than this
+    pub break_length: usize,
+    pub colors: bool,
+}
+
+impl Default for InspectOptions {
+    fn default() -> Self {
+        Self {
+            depth: Some(2),
+            max_array_length: Some(100),
+            break_length: 80,
+            colors: false,
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+enum Style {
+    Number,
+    String,
+    Undefined,
+    Null,
+    Special,
+}
+
+impl Style {
+    fn codes(self) -> (u8, u8) {
+        match self {
+            Self::Number => (33, 39),
+            Self::String => (32, 39),
+            Self::Undefined => (90, 39),
+            Self::Null => (1, 22),
+            Self::Special => (36, 39),
+        }
+    }
+}
+
+struct Inspector<'a> {
+    options: &'a InspectOptions,
+    seen: Vec<Object>,
+}
+
+impl Inspector<'_> {
+    fn paint(&self, style: Style, text: impl Into<String>) -> String {
+        let text = text.into();
+        if self.options.colors {
+            let (start, end) = style.codes();
+            format!("\x1b[{}m{}\x1b[{}m", start, text, end)
+        } else {
+            text
+        }
+    }
+
+    fn value(&mut self, value: &Value, depth: usize) -> String {
+        match value {
+            Value::Undefined => self.paint(Style::Undefined, "undefined"),
+            Value::Null => self.paint(Style::Null, "null"),
+            Value::Boolean(value) => self.paint(Style::Number, value.to_string()),
+            Value::Number(value) => self.paint(Style::Number, format_number(*value)),
+            Value::String(value) => self.paint(Style::String, quote(value)),
+            Value::Function(function) => self.function(function),
+            Value::Array(array) => self.array(array, depth),
+            Value::Object(object) => self.object(object, depth),
+        }
+    }
+
+    fn function(&self, function: &Function) -> String {
+        let name = function
+            .as_object()
+            .get("name")
+            .into_string()
+            .unwrap_or_default();
+        if name.is_empty() {
+            self.paint(Style::Special, "ƒ()")
+        } else {
+            self.paint(Style::Special, format!("{}()", name))
+        }
+    }
+
+    fn array(&mut self, array: &Array, depth: usize) -> String {
+        let object = array.as_object();
+        if self.seen.contains(&object) {
+            return self.paint(Style::Special, "[Circular]");
+        }
+        let length = array.length() as usize;
+        if length == 0 {
+            return "[]".to_owned();
+        }
+        if self.options.depth.is_some_and(|max| depth > max) {
+            return self.paint(Style::Special, "[Array]");
+        }
+        let shown = self
+            .options
+            .max_array_length
+            .map_or(length, |max| max.min(length));
+        self.seen.push(object);
+        let mut entries = (0..shown)
+            .map(|i| self.value(&array.get(i as u32), depth + 1))
+            .collect::<Vec<_>>();
+        self.seen.pop();
+        if shown < length {
+            let remaining = length - shown;
+            let plural = if remaining == 1 { "" } else { "s" };
+            entries.push(format!("... {} more item{}", remaining, plural));
+        }
+        self.join("[", entries, "]", depth)
+    }
+
+    fn object(&mut self, object: &Object, depth: usize) -> String {
+        if self.seen.contains(object) {
+            return self.paint(Style::Special, "[Circular]");
+        }
+        let description = helper(DESCRIBE)
+            .call([object.clone().into()])
+            .ok()
+            .and_then(|description| description.into_array());
+        let (is_error, class) = match description {
+            Some(description) => (
+                description.get(0).to_boolean(),
+                description.get(1).into_string(),
+            ),
+            None => (false, Some("Object".to_owned())),
+        };
+        if is_error {
+            let name = object
+                .get("name")
+                .into_string()
+                .unwrap_or_else(|| "Error".to_owned());
+            let message = object.get("message").into_string().unwrap_or_default();
+            return if message.is_empty() {
+                name
+            } else {
+                format!("{}: {}", name, message)
+            };
+        }
+        let prefix = match class.as_deref() {
+            Some("Object") => String::new(),
+            Some("") => "Object ".to_owned(),
+            Some(class) => format!("{} ", class),
+            None => "[Object: null prototype] ".to_owned(),
+        };
+        let keys = object.keys();
+        if keys.is_empty() {
+            return format!("{}{{}}", prefix);
+        }
+        if self.options.depth.is_some_and(|max| depth > max) {
+            let name = match class.as_deref() {
+                Some("") | None => "Object",
+                Some(class) => class,
+            };
+            return self.paint(Style::Special, format!("[{}]", name));
+        }
+        self.seen.push(object.clone());
+        let entries = keys
+            .iter()
+            .map(|key| {
+                format!(
+                    "{}: {}",
+                    format_key(key),
+                    self.value(&object.get(key), depth + 1)
+                )
+            })
+            .collect::<Vec<_>>();
+        self.seen.pop();
+        self.join(&format!("{}{{", prefix), entries, "}", depth)
+    }
+
+    fn join(&self, open: &str, entries: Vec<String>, close: &str, depth: usize) -> String {
+        let spaced = open.ends_with('{');
+        let length = entries
+            .iter()
+            .map(|entry| visible_length(entry) + 2)
+            .sum::<usize>()
+            + open.len()
+            + depth * 2;
+        if length <= self.options.break_length && !entries.iter().any(|entry| entry.contains('\n'))
+        {
+            if spaced {
+                format!("{} {} {}", open, entries.join(", "), close)
+            } else {
+                format!("{}{}{}", open, entries.join(", "), close)
+            }
+        } else {
+            // nested entries are already indented for their own depth
+            let indentation = "  ".repeat(depth + 1);
+            format!(
+                "{}\n{}{}\n{}{}",
+                open,
+                indentation,
+                entries.join(&format!(",\n{}", indentation)),
+                "  ".repeat(depth),
+                close
+            )
+        }
+    }
+}
+
+fn visible_length(text: &str) -> usize {
+    let mut length = 0;
+    let mut escape = false;
+    for c in text.chars() {
+        match c {
+            '\x1b' => escape = true,
+            'm' if escape => escape = false,
+            _ if escape => {}
+            _ => length += 1,
+        }
+    }
+    length
+}
+
+fn quote(string: &str) -> String {
+    let mut result = String::with_capacity(string.len() + 2);
+    result.push('"');
+    for c in string.chars() {
+        match c {
+            '"' => result.push_str("\\\""),
+            '\\' => result.push_str("\\\\"),
+            '\n' => result.push_str("\\n"),
+            '\r' => result.push_str("\\r"),
+            '\t' => result.push_str("\\t"),
+            c if c.is_control() => result.push_str(&format!("\\x{:02X}", c as u32)),
+            c => result.push(c),
+        }
+    }
+    result.push('"');
+    result
+}
+
+fn format_key(key: &str) -> String {
+    let mut chars = key.chars();
+    let identifier = chars
+        .next()
+        .is_some_and(|c| c.is_alphabetic() || c == '_' || c == '$')
+        && chars.all(|c| c.is_alphanumeric() || c == '_' || c == '$');
+    if identifier || (!key.is_empty() && key.chars().all(|c| c.is_ascii_digit())) {
+        key.to_owned()
+    } else {
+        quote(key)
+    }
+}
+
+// Number.prototype.toString, except that -0 keeps its sign like util.inspect
+pub(crate) fn format_number(value: f64) -> String {
+    if value.is_nan() {
+        return "NaN".to_owned();
+    }
+    if value.is_infinite() {
+        return if value > 0. { "Infinity" } else { "-Infinity" }.to_owned();
+    }
+    if value == 0. {
+        return if value.is_sign_negative() { "-0" } else { "0" }.to_owned();
+    }
+    let sign = if value < 0. { "-" } else { "" };
+    // shortest round-trip digits and exponent, e.g. "1.2345e21"
+    let scientific = format!("{:e}", value.abs());
+    let (mantissa, exponent) = scientific.split_once('e').unwrap();
+    let digits = mantissa.replace('.', "");
+    let k = digits.len() as i32;
+    let n = exponent.parse::<i32>().unwrap() + 1;
+    let formatted = if k <= n && n <= 21 {
+        format!("{}{}", digits, "0".repeat((n - k) as usize))
+    } else if 0 < n && n <= 21 {
+        format!("{}.{}", &digits[..n as usize], &digits[n as usize..])
+    } else if -6 < n && n <= 0 {
+        format!("0.{}{}", "0".repeat(-n as usize), digits)
+    } else {
+        let exponent = n - 1;
+        let exponent_sign = if exponent < 0 { "-" } else { "+" };
+        if k == 1 {
+            format!("{}e{}{}", digits, exponent_sign, exponent.abs())
+        } else {
+            format!(
+                "{}.{}e{}{}",
+                &digits[..1],
+                &digits[1..],
+                exponent_sign,
+                exponent.abs()
+            )
+        }
+    };
+    format!("{}{}", sign, formatted)
+}
+
+impl Value {
+    pub fn inspect(&self, options: &InspectOptions) -> String {
+        Inspector {
+            options,
+            seen: vec![],
+        }
+        .value(self, 0)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    #[cfg(engine = "browser")]
+    use wasm_bindgen_test::wasm_bindgen_test as test;
+
+    use crate::{eval, InspectOptions, Value};
+
+    fn inspect(source: &str) -> String {
+        eval(source).unwrap().inspect(&InspectOptions::default())
+    }
+
+    #[test]
+    fn numbers() {
+        assert_eq!(Value::Number(-0.).to_string(), "-0");
+        assert_eq!(Value::Number(1e21).to_string(), "1e+21");
+        assert_eq!(Value::Number(1e20).to_string(), "100000000000000000000");
+        assert_eq!(Value::Number(1.5e-7).to_string(), "1.5e-7");
+        assert_eq!(Value::Number(0.000001).to_string(), "0.000001");
+        assert_eq!(Value::Number(-123.456).to_string(), "-123.456");
+        assert_eq!(Value::Number(f64::NAN).to_string(), "NaN");
+        assert_eq!(Value::Number(f64::NEG_INFINITY).to_string(), "-Infinity");
+    }
+
+    #[test]
+    fn circular() {
+        assert_eq!(
+            inspect("{ const a = { name: 'a' }; a.self = a; a }"),
+            "{ name: \"a\", self: [Circular] }"
+        );
+        assert_eq!(
+            inspect("{ const a = [1]; a.push(a); a }"),
+            "[1, [Circular]]"
+        );
+        // the same object twice without a cycle is not circular
+        assert_eq!(inspect("{ const a = {}; [a, a] }"), "[{}, {}]");
+    }
+
+    #[test]
+    fn depth() {
+        assert_eq!(
+            inspect("({ a: { b: { c: { d: 1 } } } })"),
+            "{ a: { b: { c: [Object] } } }"
+        );
+        let options = InspectOptions {
+            depth: None,
+            ..Default::default()
+        };
+        assert_eq!(
+            eval("({ a: { b: { c: { d: [1] } } } })")
+                .unwrap()
+                .inspect(&options),
+            "{ a: { b: { c: { d: [1] } } } }"
+        );
+    }
+
+    #[test]
+    fn arrays() {
+        let options = InspectOptions {
+            max_array_length: Some(3),
+            ..Default::default()
+        };
+        assert_eq!(
+            eval("[1, 2, 3, 4, 5]").unwrap().inspect(&options),
+            "[1, 2, 3, ... 2 more items]"
+        );
+        assert_eq!(
+            inspect("Array.from({ length: 30 }, (_, i) => i * 1000)"),
+            format!(
+                "[\n{}\n]",
+                (0..30)
+                    .map(|i| format!("  {}", i * 1000))
+                    .collect::<Vec<_>>()
+                    .join(",\n")
+            )
+        );
+    }
+
+    #[test]
+    fn classes_and_errors() {
+        assert_eq!(
+            inspect("{ class Point { constructor() { this.x = 1; } } new Point() }"),
+            "Point { x: 1 }"
+        );
+        assert_eq!(
+            inspect("Object.create(null)"),
+            "[Object: null prototype] {}"
+        );
+        assert_eq!(inspect("new TypeError('bad')"), "TypeError: bad");
+        assert_eq!(inspect("({ 'a-b': 1 })"), "{ \"a-b\": 1 }");
+    }
+
+    #[test]
+    fn colors() {
+        let options = InspectOptions {
+            colors: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            eval("({ a: 1, b: 'x', c: null })")
+                .unwrap()
+                .inspect(&options),
+            "{ a: \x1b[33m1\x1b[39m, b: \x1b[32m\"x\"\x1b[39m, c: \x1b[1mnull\x1b[22m }"
+        );
+    }
+}
@@ -0,0 +1,207 @@
+use std::cell::RefCell;
+
+use crate::{value::helper, Function, Object, Value};
+
+const GET_ITERATOR: &str = "(function (iterable) { return iterable[Symbol.iterator](); })";
+const STEP: &str = "(function (iterator) {
+    const result = iterator.next();
+    if (Object(result) !== result) {
+        throw new TypeError(`Iterator result ${result} is not an object`);
+    }
+    return [!!result.done, result.value];
+})";
+const CLOSE: &str = "(function (iterator) {
+    if (typeof iterator.return === 'function') {
+        iterator.return();
+    }
+})";
+const ITERATOR: &str = "(function (next, close) {
+    return {
+        next,
+        return(value) {
+            close();
+            return { done: true, value };
+        },
+        [Symbol.iterator]() {
+            return this;
+        },
+    };
+})";
+
+// drives a JS iterator, closing it (calling `return()`) if dropped before it is exhausted
+pub struct JsIterator {
+    iterator: Value,
+    done: bool,
+}
+
+impl Iterator for JsIterator {
+    type Item = Result<Value, Value>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        let step = match helper(STEP).call([self.iterator.clone()]) {
+            Ok(step) => step.into_array().unwrap(),
+            Err(err) => {
+                self.done = true;
+                return Some(Err(err));
+            }
+        };
+        if step.get(0).to_boolean() {
+            self.done = true;
+            None
+        } else {
+            Some(Ok(step.get(1)))
+        }
+    }
+}
+
+impl Drop for JsIterator {
+    fn drop(&mut self) {
+        if !self.done {
+            let _ = helper(CLOSE).call([self.iterator.clone()]);
+        }
+    }
+}
+
+impl Value {
+    // throws a TypeError for values that aren't iterable
+    pub fn iter(&self) -> Result<JsIterator, Value> {
+        let iterator = helper(GET_ITERATOR).call([self.clone()])?;
+        Ok(JsIterator {
+            iterator,
+            done: false,
+        })
+    }
+}
+
+impl Object {
+    // a lazy iterator object usable with for...of and spread, the Rust iterator is dropped once
+    // it is exhausted or the loop exits early
+    #[allow(clippy::should_implement_trait)]
+    pub fn from_iter<I>(iter: I) -> Object
+    where
+        I: IntoIterator,
+        I::IntoIter: 'static,
+        I::Item: Into<Value> + 'static,
+    {
+        let iter: Box<dyn Iterator<Item = I::Item>> = Box::new(iter.into_iter());
+        let state = std::rc::Rc::new(RefCell::new(Some(iter)));
+        let close = {
+            let state = state.clone();
+            Function::new(move |_| {
+                state.borrow_mut().take();
+                Ok(())
+            })
+        };
+        let next = Function::new(move |_| {
+            let mut state = state.borrow_mut();
+            let value = state.as_mut().and_then(|iter| iter.next());
+            let result = Object::new();
+            match value {
+                Some(value) => {
+                    result.set("done", false);
+                    result.set("value", value);
+                }
+                None => {
+                    state.take();
+                    result.set("done", true);
+                    result.set("value", Value::Undefined);
+                }
+            }
+            Ok(result)
+        });
+        helper(ITERATOR)
+            .call([next.into(), close.into()])
+            .unwrap()
+            .into_object()
+            .unwrap()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    #[cfg(engine = "browser")]
+    use wasm_bindgen_test::wasm_bindgen_test as test;
+
+    use std::{cell::Cell, rc::Rc};
+
+    use crate::{eval, global_set, Object, Value};
+
+    fn collect(source: &str) -> Vec<Value> {
+        eval(source)
+            .unwrap()
+            .iter()
+            .unwrap()
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap()
+    }
+
+    #[test]
+    fn iterate_js() {
+        assert_eq!(
+            collect("[1, 2, 3]"),
+            vec![1.0.into(), 2.0.into(), 3.0.into()]
+        );
+        assert_eq!(
+            collect("(function* () { yield 'a'; yield 'b'; })()"),
+            vec!["a".into(), "b".into()]
+        );
+        assert_eq!(
+            collect("new Set(['x', 'x', 'y'])"),
+            vec!["x".into(), "y".into()]
+        );
+        let entries = collect("new Map([['k', 1]])");
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].as_array().unwrap().get(0), Value::from("k"));
+        assert_eq!(collect("'hé'"), vec!["h".into(), "é".into()]);
+        assert!(Value::Number(1.).iter().is_err());
+    }
+
+    #[test]
+    fn iterate_js_errors() {
+        let mut iter = eval("(function* () { yield 1; throw 'nope'; })()")
+            .unwrap()
+            .iter()
+            .unwrap();
+        assert_eq!(iter.next(), Some(Ok(1.0.into())));
+        assert_eq!(iter.next(), Some(Err("nope".into())));
+        assert_eq!(iter.next(), None);
+    }
+
+    #[test]
+    fn close_on_drop() {
+        eval("globalThis.closed = false").unwrap();
+        let mut iter =
+            eval("(function* () { try { yield 1; yield 2; } finally { closed = true; } })()")
+                .unwrap()
+                .iter()
+                .unwrap();
+        assert_eq!(iter.next(), Some(Ok(1.0.into())));
+        drop(iter);
+        assert_eq!(eval("closed").unwrap(), Value::Boolean(true));
+    }
+
+    #[test]
+    fn from_rust_iterator() {
+        global_set("numbers", Object::from_iter((1..=3).map(|i| i as f64)));
+        assert_eq!(eval("[...numbers].join()").unwrap(), Value::from("1,2,3"));
+        assert_eq!(eval("[...numbers].length").unwrap(), Value::Number(0.));
+
+        let pulled = Rc::new(Cell::new(0));
+        let counter = pulled.clone();
+        let lazy = Object::from_iter((0..).map(move |i| {
+            counter.set(counter.get() + 1);
+            i as f64
+        }));
+        global_set("lazy", lazy);
+        assert_eq!(
+            eval("{ let sum = 0; for (const i of lazy) { if (i > 2) break; sum += i; } sum }")
+                .unwrap(),
+            Value::Number(3.)
+        );
+        assert_eq!(pulled.get(), 4);
+        assert_eq!(eval("lazy.next().done").unwrap(), Value::Boolean(true));
+    }
+}
@@ -0,0 +1,1129 @@
+use url::{form_urlencoded, quirks, Url};
+
+use crate::{eval, global_get, global_set, Array, AsObject, Exception, Function, Object, Value};
+
+// the encoding, base64, URL and query parsing and the structured clone walk are Rust natives. The
+// glue only declares the classes around them, checks arguments and throws the spec's exceptions,
+// and carries a small ReadableStream (default readers only) for `fetch` bodies and `stream`
+const GLUE: &str = r#"(function (native) {
+    "use strict";
+
+    class DOMException extends Error {
+        #name;
+
+        constructor(message = "", name = "Error") {
+            super(message);
+            this.#name = String(name);
+        }
+
+        get name() {
+            return this.#name;
+        }
+    }
+
+    function requireArguments(length, required, name) {
+        if (length < required) {
+            throw new TypeError(`${name}: ${required} argument(s) required, but only ${length} present.`);
+        }
+    }
+
+    function isBufferSource(value) {
+        return value instanceof ArrayBuffer || ArrayBuffer.isView(value);
+    }
+
+    class TextEncoder {
+        get encoding() {
+            return "utf-8";
+        }
+
+        encode(input = "") {
+            return native.encode(String(input));
+        }
+
+        encodeInto(source, destination) {
+            requireArguments(arguments.length, 2, "TextEncoder.encodeInto");
+            if (!(destination instanceof Uint8Array)) {
+                throw new TypeError("The destination must be a Uint8Array.");
+            }
+            const [read, bytes] = native.encodeInto(String(source), destination.length);
+            destination.set(bytes);
+            return { read, written: bytes.length };
+        }
+    }
+
+    const utf8Labels = [
+        "unicode-1-1-utf-8", "unicode11utf8", "unicode20utf8", "utf-8", "utf8", "x-unicode20utf8",
+    ];
+
+    class TextDecoder {
+        #fatal;
+        #ignoreBOM;
+
+        // UTF-8 only, the legacy encodings of the Encoding standard are rejected like unknown labels
+        constructor(label = "utf-8", options = {}) {
+            label = String(label).trim().toLowerCase();
+            if (!utf8Labels.includes(label)) {
+                throw new RangeError(`The encoding label provided ('${label}') is invalid.`);
+            }
+            this.#fatal = Boolean(options.fatal);
+            this.#ignoreBOM = Boolean(options.ignoreBOM);
+        }
+
+        get encoding() {
+            return "utf-8";
+        }
+
+        get fatal() {
+            return this.#fatal;
+        }
+
+        get ignoreBOM() {
+            return this.#ignoreBOM;
+        }
+
+        decode(input = new Uint8Array(0)) {
+            if (!isBufferSource(input)) {
+                throw new TypeError("The provided value is not of type '(ArrayBuffer or ArrayBufferView)'.");
+            }
+            const string = native.decode(input, this.#fatal, this.#ignoreBOM);
+            if (string === null) {
+                throw new TypeError("The encoded data was not valid.");
+            }
+            return string;
+        }
+    }
+
+    function btoa(data) {
+        requireArguments(arguments.length, 1, "btoa");
+        const result = native.btoa(String(data));
+        if (result === null) {
+            throw new DOMException("Invalid character", "InvalidCharacterError");
+        }
+        return result;
+    }
+
+    function atob(data) {
+        requireArguments(arguments.length, 1, "atob");
+        const result = native.atob(String(data));
+        if (result === null) {
+            throw new DOMException("The string to be decoded is not correctly encoded.", "InvalidCharacterError");
+        }
+        return result;
+    }
+
+    let attachSearchParams;
+    let updateSearchParams;
+    let setUrlSearch;
+
+    class URLSearchParams {
+        #list = [];
+        #url = null;
+
+        static {
+            attachSearchParams = (params, url) => {
+                params.#url = url;
+            };
+            updateSearchParams = (params, search) => {
+                params.#list = native.parseQuery(search);
+            };
+        }
+
+        constructor(init = "") {
+            if (typeof init === "object" && init !== null) {
+                if (typeof init[Symbol.iterator] === "function") {
+                    for (const pair of init) {
+                        const entry = [...pair];
+                        if (entry.length !== 2) {
+                            throw new TypeError("Each query pair must be an iterable [name, value] tuple.");
+                        }
+                        this.#list.push([String(entry[0]), String(entry[1])]);
+                    }
+                } else {
+                    for (const key of Object.keys(init)) {
+                        this.#list.push([key, String(init[key])]);
+                    }
+                }
+            } else {
+                init = String(init);
+                this.#list = native.parseQuery(init.startsWith("?") ? init.slice(1) : init);
+            }
+        }
+
+        #update() {
+            if (this.#url !== null) {
+                setUrlSearch(this.#url, this.toString());
+            }
+        }
+
+        get size() {
+            return this.#list.length;
+        }
+
+        append(name, value) {
+            requireArguments(arguments.length, 2, "URLSearchParams.append");
+            this.#list.push([String(name), String(value)]);
+            this.#update();
+        }
+
+        delete(name, value) {
+            requireArguments(arguments.length, 1, "URLSearchParams.delete");
+            name = String(name);
+            value = value === undefined ? undefined : String(value);
+            this.#list = this.#list.filter(([n, v]) => n !== name || (value !== undefined && v !== value));
+            this.#update();
+        }
+
+        get(name) {
+            requireArguments(arguments.length, 1, "URLSearchParams.get");
+            name = String(name);
+            const entry = this.#list.find(([n]) => n === name);
+            return entry === undefined ? null : entry[1];
+        }
+
+        getAll(name) {
+            requireArguments(arguments.length, 1, "URLSearchParams.getAll");
+            name = String(name);
+            return this.#list.filter(([n]) => n === name).map(([, v]) => v);
+        }
+
+        has(name, value) {
+            requireArguments(arguments.length, 1, "URLSearchParams.has");
+            name = String(name);
+            value = value === undefined ? undefined : String(value);
+            return this.#list.some(([n, v]) => n === name && (value === undefined || v === value));
+        }
+
+        set(name, value) {
+            requireArguments(arguments.length, 2, "URLSearchParams.set");
+            name = String(name);
+            value = String(value);
+            const index = this.#list.findIndex(([n]) => n === name);
+            if (index === -1) {
+                this.#list.push([name, value]);
+            } else {
+                this.#list[index][1] = value;
+                this.#list = this.#list.filter(([n], i) => n !== name || i <= index);
+            }
+            this.#update();
+        }
+
+        sort() {
+            this.#list = [...this.#list].sort(([a], [b]) => (a < b ? -1 : a > b ? 1 : 0));
+            this.#update();
+        }
+
+        forEach(callback, thisArg) {
+            for (const [name, value] of this) {
+                callback.call(thisArg, value, name, this);
+            }
+        }
+
+        *entries() {
+            for (let i = 0; i < this.#list.length; i++) {
+                yield [this.#list[i][0], this.#list[i][1]];
+            }
+        }
+
+        *keys() {
+            for (const [name] of this.entries()) {
+                yield name;
+            }
+        }
+
+        *values() {
+            for (const [, value] of this.entries()) {
+                yield value;
+            }
+        }
+
+        [Symbol.iterator]() {
+            return this.entries();
+        }
+
+        toString() {
+            return native.serializeQuery(this.#list);
+        }
+    }
+
+    function parseUrl(url, base) {
+        return native.parseUrl(String(url), base === undefined ? undefined : String(base));
+    }
+
+    class URL {
+        #parts;
+        #searchParams = null;
+
+        static {
+            setUrlSearch = (url, search) => {
+                url.#parts = native.setUrl(url.#parts.href, "search", search);
+            };
+        }
+
+        constructor(url, base) {
+            requireArguments(arguments.length, 1, "URL");
+            const parts = parseUrl(url, base);
+            if (parts === null) {
+                throw new TypeError(`Invalid URL: ${url}`);
+            }
+            this.#parts = parts;
+        }
+
+        static canParse(url, base) {
+            requireArguments(arguments.length, 1, "URL.canParse");
+            return parseUrl(url, base) !== null;
+        }
+
+        static parse(url, base) {
+            requireArguments(arguments.length, 1, "URL.parse");
+            const parts = parseUrl(url, base);
+            return parts === null ? null : new URL(parts.href);
+        }
+
+        #set(component, value) {
+            this.#parts = native.setUrl(this.#parts.href, component, String(value));
+            if (this.#searchParams !== null) {
+                updateSearchParams(this.#searchParams, this.#parts.search.slice(1));
+            }
+        }
+
+        get href() {
+            return this.#parts.href;
+        }
+
+        set href(value) {
+            const parts = parseUrl(value);
+            if (parts === null) {
+                throw new TypeError(`Invalid URL: ${value}`);
+            }
+            this.#parts = parts;
+            if (this.#searchParams !== null) {
+                updateSearchParams(this.#searchParams, this.#parts.search.slice(1));
+            }
+        }
+
+        get origin() {
+            return this.#parts.origin;
+        }
+
+        get protocol() {
+            return this.#parts.protocol;
+        }
+
+        set protocol(value) {
+            this.#set("protocol", value);
+        }
+
+        get username() {
+            return this.#parts.username;
+        }
+
+        set username(value) {
+            this.#set("username", value);
+        }
+
+        get password() {
+            return this.#parts.password;
+        }
+
+        set password(value) {
+            this.#set("password", value);
+        }
+
+        get host() {
+            return this.#parts.host;
+        }
+
+        set host(value) {
+            this.#set("host", value);
+        }
+
+        get hostname() {
+            return this.#parts.hostname;
+        }
+
+        set hostname(value) {
+            this.#set("hostname", value);
+        }
+
+        get port() {
+            return this.#parts.port;
+        }
+
+        set port(value) {
+            this.#set("port", value);
+        }
+
+        get pathname() {
+            return this.#parts.pathname;
+        }
+
+        set pathname(value) {
+            this.#set("pathname", value);
+        }
+
+        get search() {
+            return this.#parts.search;
+        }
+
+        set search(value) {
+            this.#set("search", value);
+        }
+
+        get searchParams() {
+            if (this.#searchParams === null) {
+                this.#searchParams = new URLSearchParams(this.#parts.search);
+                attachSearchParams(this.#searchParams, this);
+            }
+            return this.#searchParams;
+        }
+
+        get hash() {
+            return this.#parts.hash;
+        }
+
+        set hash(value) {
+            this.#set("hash", value);
+        }
+
+        toString() {
+            return this.href;
+        }
+
+        toJSON() {
+            return this.href;
+        }
+    }
+
+    const streamStates = new WeakMap();
+
+    function streamState(stream) {
+        const state = streamStates.get(stream);
+        if (state === undefined) {
+            throw new TypeError("Illegal invocation");
+        }
+        return state;
+    }
+
+    function pullIfNeeded(state) {
+        if (!state.started || state.status !== "readable" || state.closeRequested) {
+            return;
+        }
+        if (state.reads.length === 0 && state.queue.length >= state.highWaterMark) {
+            return;
+        }
+        if (state.pulling) {
+            state.pullAgain = true;
+            return;
+        }
+        state.pulling = true;
+        Promise.resolve()
+            .then(() => state.source.pull?.(state.controller))
+            .then(() => {
+                state.pulling = false;
+                if (state.pullAgain) {
+                    state.pullAgain = false;
+                    pullIfNeeded(state);
+                }
+            }, (reason) => errorStream(state, reason));
+    }
+
+    function finishClose(state) {
+        state.status = "closed";
+        for (const read of state.reads.splice(0)) {
+            read.resolve({ value: undefined, done: true });
+        }
+    }
+
+    function errorStream(state, reason) {
+        if (state.status !== "readable") {
+            return;
+        }
+        state.status = "errored";
+        state.storedError = reason;
+        state.queue = [];
+        for (const read of state.reads.splice(0)) {
+            read.reject(reason);
+        }
+    }
+
+    function readChunk(state) {
+        if (state.queue.length > 0) {
+            const value = state.queue.shift();
+            if (state.closeRequested && state.queue.length === 0) {
+                finishClose(state);
+            } else {
+                pullIfNeeded(state);
+            }
+            return Promise.resolve({ value, done: false });
+        }
+        if (state.status === "closed") {
+            return Promise.resolve({ value: undefined, done: true });
+        }
+        if (state.status === "errored") {
+            return Promise.reject(state.storedError);
+        }
+        const promise = new Promise((resolve, reject) => state.reads.push({ resolve, reject }));
+        pullIfNeeded(state);
+        return promise;
+    }
+
+    function cancelStream(state, reason) {
+        if (state.status === "closed") {
+            return Promise.resolve();
+        }
+        if (state.status === "errored") {
+            return Promise.reject(state.storedError);
+        }
+        state.queue = [];
+        finishClose(state);
+        return Promise.resolve(state.source.cancel?.(reason)).then(() => undefined);
+    }
+
+    class ReadableStreamDefaultController {
+        #state;
+
+        constructor(state) {
+            this.#state = state;
+        }
+
+        get desiredSize() {
+            const state = this.#state;
+            if (state.status === "errored") {
+                return null;
+            }
+            return state.status === "closed" ? 0 : state.highWaterMark - state.queue.length;
+        }
+
+        enqueue(chunk) {
+            const state = this.#state;
+            if (state.status !== "readable" || state.closeRequested) {
+                throw new TypeError("The stream is not in a state that permits enqueue");
+            }
+            const read = state.reads.shift();
+            if (read !== undefined) {
+                read.resolve({ value: chunk, done: false });
+            } else {
+                state.queue.push(chunk);
+            }
+            pullIfNeeded(state);
+        }
+
+        close() {
+            const state = this.#state;
+            if (state.status !== "readable" || state.closeRequested) {
+                throw new TypeError("The stream is not in a state that permits close");
+            }
+            state.closeRequested = true;
+            if (state.queue.length === 0) {
+                finishClose(state);
+            }
+        }
+
+        error(reason) {
+            errorStream(this.#state, reason);
+        }
+    }
+
+    class ReadableStreamDefaultReader {
+        #state;
+
+        constructor(stream) {
+            const state = streamState(stream);
+            if (state.reader !== null) {
+                throw new TypeError("ReadableStream is locked");
+            }
+            state.reader = this;
+            this.#state = state;
+        }
+
+        read() {
+            if (this.#state === null) {
+                return Promise.reject(new TypeError("The reader has been released"));
+            }
+            return readChunk(this.#state);
+        }
+
+        cancel(reason) {
+            if (this.#state === null) {
+                return Promise.reject(new TypeError("The reader has been released"));
+            }
+            return cancelStream(this.#state, reason);
+        }
+
+        releaseLock() {
+            const state = this.#state;
+            if (state === null) {
+                return;
+            }
+            state.reader = null;
+            for (const read of state.reads.splice(0)) {
+                read.reject(new TypeError("The reader has been released"));
+            }
+            this.#state = null;
+        }
+    }
+
+    class ReadableStream {
+        constructor(source = {}, strategy = {}) {
+            const state = {
+                status: "readable",
+                queue: [],
+                reads: [],
+                highWaterMark: strategy.highWaterMark ?? 1,
+                source,
+                started: false,
+                pulling: false,
+                pullAgain: false,
+                closeRequested: false,
+                storedError: undefined,
+                reader: null,
+            };
+            state.controller = new ReadableStreamDefaultController(state);
+            streamStates.set(this, state);
+            Promise.resolve(source.start?.(state.controller)).then(() => {
+                state.started = true;
+                pullIfNeeded(state);
+            }, (reason) => errorStream(state, reason));
+        }
+
+        get locked() {
+            return streamState(this).reader !== null;
+        }
+
+        cancel(reason) {
+            const state = streamState(this);
+            if (state.reader !== null) {
+                return Promise.reject(new TypeError("ReadableStream is locked"));
+            }
+            return cancelStream(state, reason);
+        }
+
+        getReader() {
+            return new ReadableStreamDefaultReader(this);
+        }
+
+        [Symbol.asyncIterator]() {
+            const reader = this.getReader();
+            return {
+                next: () => reader.read().then((result) => {
+                    if (result.done) {
+                        reader.releaseLock();
+                    }
+                    return result;
+                }),
+                return: (value) => reader.cancel(value).then(() => {
+                    reader.releaseLock();
+                    return { value, done: true };
+                }),
+                [Symbol.asyncIterator]() {
+                    return this;
+                },
+            };
+        }
+    }
+
+    const cloneHelpers = {
+        tag: (value) => Object.prototype.toString.call(value).slice(8, -1),
+        leaf(value, tag) {
+            switch (tag) {
+                case "Boolean":
+                    return new Boolean(value.valueOf());
+                case "Number":
+                    return new Number(value.valueOf());
+                case "String":
+                    return new String(value.valueOf());
+                case "Date":
+                    return new Date(value.getTime());
+                case "RegExp":
+                    return new RegExp(value.source, value.flags);
+                case "ArrayBuffer":
+                    return value.slice(0);
+                case "DataView":
+                    return new DataView(value.buffer.slice(0), value.byteOffset, value.byteLength);
+                case "Error": {
+                    const constructors = { EvalError, RangeError, ReferenceError, SyntaxError, TypeError, URIError };
+                    const error = new (constructors[value.name] ?? Error)(value.message);
+                    if (value.stack !== undefined) {
+                        error.stack = value.stack;
+                    }
+                    return error;
+                }
+                default:
+                    if (ArrayBuffer.isView(value)) {
+                        return new value.constructor(value.buffer.slice(0), value.byteOffset, value.length);
+                    }
+                    return undefined;
+            }
+        },
+        newMap: () => new Map(),
+        newSet: () => new Set(),
+        entries: (value) => [...value.entries()],
+        mapSet: (map, key, value) => {
+            map.set(key, value);
+        },
+        setAdd: (set, value) => {
+            set.add(value);
+        },
+        error: (message) => new DOMException(message, "DataCloneError"),
+    };
+
+    function structuredClone(value) {
+        requireArguments(arguments.length, 1, "structuredClone");
+        if (typeof value === "symbol" || typeof value === "bigint") {
+            throw cloneHelpers.error(`${String(value)} could not be cloned.`);
+        }
+        return native.structuredClone(value, cloneHelpers);
+    }
+
+    return {
+        DOMException,
+        ReadableStream,
+        ReadableStreamDefaultController,
+        ReadableStreamDefaultReader,
+        TextEncoder,
+        TextDecoder,
+        URL,
+        URLSearchParams,
+        atob,
+        btoa,
+        structuredClone,
+    };
+})"#;
+
+pub(crate) fn install() {
+    let native = Object::new();
+    native.set(
+        "encode",
+        Function::new(|args| {
+            let string = args.get(0).into_string().unwrap_or_default();
+            Ok(from_bytes(string.into_bytes()))
+        }),
+    );
+    native.set(
+        "encodeInto",
+        Function::new(|args| {
+            let string = args.get(0).into_string().unwrap_or_default();
+            let capacity = args.get(1).into_number().unwrap_or(0.) as usize;
+            let mut read = 0;
+            let mut bytes = Vec::new();
+            for char in string.chars() {
+                if bytes.len() + char.len_utf8() > capacity {
+                    break;
+                }
+                let mut buffer = [0; 4];
+                bytes.extend_from_slice(char.encode_utf8(&mut buffer).as_bytes());
+                read += char.len_utf16();
+            }
+            Ok(Value::from(vec![(read as f64).into(), from_bytes(bytes)]))
+        }),
+    );
+    native.set(
+        "decode",
+        Function::new(|args| {
+            let bytes = to_bytes(args.get(0)).unwrap_or_default();
+            let fatal = args.get(1).into_boolean().unwrap_or(false);
+            let ignore_bom = args.get(2).into_boolean().unwrap_or(false);
+            let bytes = match bytes.strip_prefix(&[0xEF, 0xBB, 0xBF]) {
+                Some(stripped) if !ignore_bom => stripped,
+                _ => &bytes[..],
+            };
+            if fatal {
+                Ok(std::str::from_utf8(bytes)
+                    .map(Value::from)
+                    .unwrap_or(Value::Null))
+            } else {
+                Ok(Value::from(String::from_utf8_lossy(bytes).into_owned()))
+            }
+        }),
+    );
+    native.set(
+        "btoa",
+        Function::new(|args| {
+            let string = args.get(0).into_string().unwrap_or_default();
+            let mut bytes = Vec::new();
+            for char in string.chars() {
+                let Ok(byte) = u8::try_from(char as u32) else {
+                    return Ok(Value::Null);
+                };
+                bytes.push(byte);
+            }
+            Ok(Value::from(base64_encode(&bytes)))
+        }),
+    );
+    native.set(
+        "atob",
+        Function::new(|args| {
+            let string = args.get(0).into_string().unwrap_or_default();
+            Ok(match base64_decode(&string) {
+                Some(bytes) => Value::from(bytes.into_iter().map(char::from).collect::<String>()),
+                None => Value::Null,
+            })
+        }),
+    );
+    native.set(
+        "parseUrl",
+        Function::new(|args| {
+            let input = args.get(0).into_string().unwrap_or_default();
+            let url = match args.get(1).into_string() {
+                Some(base) => Url::parse(&base)
+                    .and_then(|base| Url::options().base_url(Some(&base)).parse(&input)),
+                None => Url::parse(&input),
+            };
+            Ok(match url {
+                Ok(url) => Value::from(url_parts(&url)),
+                Err(..) => Value::Null,
+            })
+        }),
+    );
+    native.set(
+        "setUrl",
+        Function::new(|args| {
+            let href = args.get(0).into_string().unwrap_or_default();
+            let component = args.get(1).into_string().unwrap_or_default();
+            let value = args.get(2).into_string().unwrap_or_default();
+            let mut url = Url::parse(&href).map_err(|err| Exception::msg(err.to_string()))?;
+            // setters that fail leave the URL untouched, as the URL spec requires
+            match component.as_str() {
+                "protocol" => quirks::set_protocol(&mut url, &value).unwrap_or(()),
+                "username" => quirks::set_username(&mut url, &value).unwrap_or(()),
+                "password" => quirks::set_password(&mut url, &value).unwrap_or(()),
+                "host" => quirks::set_host(&mut url, &value).unwrap_or(()),
+                "hostname" => quirks::set_hostname(&mut url, &value).unwrap_or(()),
+                "port" => quirks::set_port(&mut url, &value).unwrap_or(()),
+                "pathname" => quirks::set_pathname(&mut url, &value),
+                "search" => quirks::set_search(&mut url, &value),
+                "hash" => quirks::set_hash(&mut url, &value),
+                _ => {}
+            }
+            Ok(url_parts(&url))
+        }),
+    );
+    native.set(
+        "parseQuery",
+        Function::new(|args| {
+            let query = args.get(0).into_string().unwrap_or_default();
+            let pairs = form_urlencoded::parse(query.as_bytes())
+                .map(|(name, value)| {
+                    Value::from(vec![
+                        Value::from(name.into_owned()),
+                        Value::from(value.into_owned()),
+                    ])
+                })
+                .collect::<Vec<_>>();
+            Ok(pairs)
+        }),
+    );
+    native.set(
+        "serializeQuery",
+        Function::new(|args| {
+            let list = args.get(0).into_array().unwrap_or_else(Array::new);
+            let mut serializer = form_urlencoded::Serializer::new(String::new());
+            for i in 0..list.length() {
+                let pair = list.get(i).into_array().unwrap_or_else(Array::new);
+                let name = pair.get(0).into_string().unwrap_or_default();
+                let value = pair.get(1).into_string().unwrap_or_default();
+                serializer.append_pair(&name, &value);
+            }
+            Ok(serializer.finish())
+        }),
+    );
+    native.set(
+        "structuredClone",
+        Function::new(|args| {
+            let helpers = args.get(1).into_object().unwrap();
+            structured_clone(args.get(0), &helpers, &mut Vec::new())
+        }),
+    );
+
+    let factory = eval(GLUE).unwrap().into_function().unwrap();
+    let globals = factory.call([native.into()]).unwrap().into_object().unwrap();
+    for name in globals.keys() {
+        if global_get(&name).is_undefined() {
+            global_set(&name, globals.get(&name));
+        }
+    }
+}
+
+fn url_parts(url: &Url) -> Object {
+    let parts = Object::new();
+    parts.set("href", quirks::href(url));
+    parts.set("origin", quirks::origin(url));
+    parts.set("protocol", quirks::protocol(url));
+    parts.set("username", quirks::username(url));
+    parts.set("password", quirks::password(url));
+    parts.set("host", quirks::host(url));
+    parts.set("hostname", quirks::hostname(url));
+    parts.set("port", quirks::port(url));
+    parts.set("pathname", quirks::pathname(url));
+    parts.set("search", quirks::search(url));
+    parts.set("hash", quirks::hash(url));
+    parts
+}
+
+fn structured_clone(
+    value: Value,
+    helpers: &Object,
+    memory: &mut Vec<(Value, Value)>,
+) -> Result<Value, Exception> {
+    let helper = |name: &str, args: Vec<Value>| helpers.get(name).into_function().unwrap().call(args);
+    let object = match &value {
+        Value::Array(array) => array.as_object(),
+        Value::Object(object) => object.clone(),
+        Value::Function(..) => {
+            return Err(helper("error", vec!["Functions can not be cloned.".into()])?.into())
+        }
+        _ => return Ok(value),
+    };
+    if let Some((_, clone)) = memory.iter().find(|(original, _)| *original == value) {
+        return Ok(clone.clone());
+    }
+    if let Value::Array(array) = &value {
+        let clone = Array::new_with_length(array.length());
+        memory.push((value.clone(), clone.clone().into()));
+        for i in 0..array.length() {
+            clone.set(i, structured_clone(array.get(i), helpers, memory)?);
+        }
+        return Ok(clone.into());
+    }
+    let tag = helper("tag", vec![value.clone()])?
+        .into_string()
+        .unwrap_or_default();
+    match tag.as_str() {
+        "Object" => {
+            let clone = Object::new();
+            memory.push((value.clone(), clone.clone().into()));
+            for key in object.keys() {
+                clone.set(&key, structured_clone(object.get(&key), helpers, memory)?);
+            }
+            Ok(clone.into())
+        }
+        "Map" | "Set" => {
+            let clone = helper(if tag == "Map" { "newMap" } else { "newSet" }, vec![])?;
+            memory.push((value.clone(), clone.clone()));
+            let entries = helper("entries", vec![value.clone()])?
+                .into_array()
+                .unwrap();
+            for i in 0..entries.length() {
+                let entry = entries.get(i).into_array().unwrap();
+                let key = structured_clone(entry.get(0), helpers, memory)?;
+                if tag == "Map" {
+                    let value = structured_clone(entry.get(1), helpers, memory)?;
+                    helper("mapSet", vec![clone.clone(), key, value])?;
+                } else {
+                    helper("setAdd", vec![clone.clone(), key])?;
+                }
+            }
+            Ok(clone)
+        }
+        _ => {
+            let clone = helper("leaf", vec![value.clone(), tag.clone().into()])?;
+            if matches!(clone, Value::Undefined) {
+                let message = format!("{} object could not be cloned.", tag);
+                return Err(helper("error", vec![message.into()])?.into());
+            }
+            memory.push((value, clone.clone()));
+            Ok(clone)
+        }
+    }
+}
+
+const BASE64: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+fn base64_encode(bytes: &[u8]) -> String {
+    let mut string = String::with_capacity((bytes.len() + 2) / 3 * 4);
+    for chunk in bytes.chunks(3) {
+        let b = [
+            chunk[0],
+            chunk.get(1).copied().unwrap_or(0),
+            chunk.get(2).copied().unwrap_or(0),
+        ];
+        let n = (b[0] as u32) << 16 | (b[1] as u32) << 8 | b[2] as u32;
+        for i in 0..4 {
+            if i <= chunk.len() {
+                string.push(BASE64[(n >> (18 - i * 6)) as usize & 63] as char);
+            } else {
+                string.push('=');
+            }
+        }
+    }
+    string
+}
+
+// https://infra.spec.whatwg.org/#forgiving-base64-decode
+fn base64_decode(string: &str) -> Option<Vec<u8>> {
+    let mut data = string
+        .chars()
+        .filter(|char| !matches!(char, '\t' | '\n' | '\x0C' | '\r' | ' '))
+        .collect::<Vec<_>>();
+    if data.len() % 4 == 0 {
+        for _ in 0..2 {
+            if data.last() == Some(&'=') {
+                data.pop();
+            }
+        }
+    }
+    if data.len() % 4 == 1 {
+        return None;
+    }
+    let mut bytes = Vec::with_capacity(data.len() * 3 / 4);
+    let mut buffer = 0u32;
+    let mut bits = 0;
+    for char in data {
+        let index = BASE64.iter().position(|c| *c as char == char)? as u32;
+        buffer = buffer << 6 | index;
+        bits += 6;
+        if bits >= 8 {
+            bits -= 8;
+            bytes.push((buffer >> bits) as u8);
+            buffer &= (1 << bits) - 1;
+        }
+    }
+    Some(bytes)
+}
+
+pub(crate) fn to_bytes(value: Value) -> Option<Vec<u8>> {
+    let scope = crate::v8::scope();
+    let value = v8::Local::<v8::Value>::from(value);
+    let view = if let Ok(view) = v8::Local::<v8::ArrayBufferView>::try_from(value) {
+        view
+    } else {
+        let buffer = v8::Local::<v8::ArrayBuffer>::try_from(value).ok()?;
+        let length = buffer.byte_length();
+        v8::Uint8Array::new(scope, buffer, 0, length)?.into()
+    };
+    let mut bytes = vec![0; view.byte_length()];
+    view.copy_contents(&mut bytes);
+    Some(bytes)
+}
+
+pub(crate) fn from_bytes(bytes: Vec<u8>) -> Value {
+    let scope = crate::v8::scope();
+    let length = bytes.len();
+    let store = v8::ArrayBuffer::new_backing_store_from_vec(bytes).make_shared();
+    let buffer = v8::ArrayBuffer::with_backing_store(scope, &store);
+    let array = v8::Uint8Array::new(scope, buffer, 0, length).unwrap();
+    Value::from(v8::Local::<v8::Value>::from(array))
+}
+
+#[cfg(test)]
+mod test {
+    use crate::Value;
+
+    fn eval(source: impl AsRef<str>) -> Value {
+        crate::eval(source).unwrap()
+    }
+
+    #[test]
+    fn text_encoding() {
+        assert_eq!(
+            eval("new TextEncoder().encode('hé💖').join()"),
+            Value::from("104,195,169,240,159,146,150")
+        );
+        assert_eq!(
+            eval("new TextDecoder().decode(new Uint8Array([0xEF, 0xBB, 0xBF, 104, 195, 169]))"),
+            Value::from("hé")
+        );
+        assert_eq!(
+            eval("new TextDecoder().decode(new Uint8Array([104, 0xFF]))"),
+            Value::from("h\u{FFFD}")
+        );
+        assert!(crate::eval("new TextDecoder('utf-8', { fatal: true }).decode(new Uint8Array([0xFF]))").is_err());
+        assert_eq!(eval("new TextDecoder(' UTF8 ').encoding"), Value::from("utf-8"));
+        assert_eq!(
+            eval("try { new TextDecoder('utf-16le') } catch (e) { e instanceof RangeError }"),
+            Value::from(true)
+        );
+        assert_eq!(
+            eval(
+                "{
+                    const bytes = new Uint8Array(4);
+                    const { read, written } = new TextEncoder().encodeInto('a💖', bytes);
+                    `${read},${written}`
+                }"
+            ),
+            Value::from("1,1")
+        );
+    }
+
+    #[test]
+    fn base64() {
+        assert_eq!(eval("btoa('hello')"), Value::from("aGVsbG8="));
+        assert_eq!(eval("btoa('\\xFF\\xFE')"), Value::from("//4="));
+        assert_eq!(eval("atob('aGVsbG8=')"), Value::from("hello"));
+        assert_eq!(eval("atob(' aGVs bG8 ')"), Value::from("hello"));
+        assert_eq!(
+            eval("try { btoa('💖') } catch (e) { e.name }"),
+            Value::from("InvalidCharacterError")
+        );
+        assert!(crate::eval("atob('a')").is_err());
+    }
+
+    #[test]
+    fn url() {
+        assert_eq!(
+            eval("new URL('../c?x=1#y', 'https://user@example.com:8080/a/b').href"),
+            Value::from("https://user@example.com:8080/c?x=1#y")
+        );
+        assert_eq!(
+            eval("new URL('https://example.com:443/').origin"),
+            Value::from("https://example.com")
+        );
+        assert_eq!(
+            eval(
+                "{
+                    const url = new URL('https://example.com/?a=1');
+                    url.searchParams.append('b', 'two words');
+                    url.pathname = '/path';
+                    url.href
+                }"
+            ),
+            Value::from("https://example.com/path?a=1&b=two+words")
+        );
+        assert_eq!(eval("URL.canParse('not a url')"), Value::Boolean(false));
+        assert!(crate::eval("new URL('not a url')").is_err());
+    }
+
+    #[test]
+    fn url_search_params() {
+        assert_eq!(
+            eval(
+                "{
+                    const params = new URLSearchParams('?b=2&a=1&b=3');
+                    params.sort();
+                    params.set('c', 'ü');
+                    `${params.getAll('b')}|${params}`
+                }"
+            ),
+            Value::from("2,3|a=1&b=2&b=3&c=%C3%BC")
+        );
+        assert_eq!(
+            eval("[...new URLSearchParams({ x: 1, y: 'z' }).keys()].join()"),
+            Value::from("x,y")
+        );
+    }
+
+    #[test]
+    fn structured_clone() {
+        assert_eq!(
+            eval(
+                "{
+                    const original = { date: new Date(5), map: new Map([[1, { n: 2 }]]), list: [1, 'a'] };
+                    original.self = original;
+                    const clone = structuredClone(original);
+                    [
+                        clone !== original,
+                        clone.self === clone,
+                        clone.date.getTime() === 5,
+                        clone.map.get(1).n === 2,
+                        clone.list.join() === '1,a',
+                    ].every(Boolean)
+                }"
+            ),
+            Value::Boolean(true)
+        );
+        assert_eq!(
+            eval("try { structuredClone({ f() {} }) } catch (e) { e.name }"),
+            Value::from("DataCloneError")
+        );
+    }
+}
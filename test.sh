@@ -1,3 +1,6 @@
 set -e
 cargo test --  --test-threads=1
 cargo test --target wasm32-unknown-unknown --config "target.wasm32-unknown-unknown.runner = 'wasm-bindgen-test-runner'"
+cargo test --no-default-features --features quickjs -- --test-threads=1
+cargo test --target wasm32-wasip1 --no-default-features --features quickjs --config "target.wasm32-wasip1.runner = 'wasmtime'"
+cargo test --no-default-features --features boa -- --test-threads=1
@@ -0,0 +1,52 @@
+// runtimes start their own threads, which would share the test thread's engine if they ran as
+// unit tests, and there are no threads on wasm
+#![cfg(not(target_arch = "wasm32"))]
+
+use futures::executor::block_on;
+use unijs3::{eval, runtime::RuntimeHandle, Value};
+
+#[test]
+fn shared_runtime() {
+    let handle = RuntimeHandle::new();
+    let worker = handle.clone();
+    let result =
+        std::thread::spawn(move || block_on(worker.eval("globalThis.shared = 40; shared + 2")))
+            .join()
+            .unwrap();
+    assert_eq!(result, Ok(Some("42".to_owned())));
+    let shared = block_on(handle.run(|runtime| runtime.eval("shared").unwrap().to_string()));
+    assert_eq!(shared, "40");
+    assert!(block_on(handle.eval("throw new Error('nope')"))
+        .unwrap_err()
+        .contains("nope"));
+}
+
+#[test]
+fn syntax_errors_are_returned() {
+    let handle = RuntimeHandle::new();
+    let err = block_on(handle.eval("1 +")).unwrap_err();
+    assert!(err.contains("SyntaxError"), "{}", err);
+    assert_eq!(block_on(handle.eval("1 + 1")), Ok(Some("2".to_owned())));
+}
+
+#[test]
+fn separate_engines() {
+    let first = RuntimeHandle::new();
+    let second = RuntimeHandle::new();
+    block_on(first.eval("globalThis.owner = 'first'")).unwrap();
+    assert_eq!(
+        block_on(second.eval("typeof owner")),
+        Ok(Some("\"undefined\"".to_owned()))
+    );
+    assert_eq!(eval("typeof owner").unwrap(), Value::from("undefined"));
+}
+
+#[test]
+fn panics_reach_the_caller() {
+    let handle = RuntimeHandle::new();
+    let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+        block_on(handle.run::<()>(|_| panic!("boom")))
+    }));
+    assert!(result.is_err());
+    assert_eq!(block_on(handle.eval("1 + 1")), Ok(Some("2".to_owned())));
+}